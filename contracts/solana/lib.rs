@@ -1,9 +1,26 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_spl::token::{self, Burn, CloseAccount, Mint, MintTo, Token, TokenAccount, Transfer};
 use pyth_sdk_solana::load_price_feed_from_account_info;
 
 declare_id!("DexFree1111111111111111111111111111111111111");
 
+pub const MAX_SYMBOL_LEN: usize = 16;
+pub const FUNDING_PRECISION: i128 = 1_000_000_000_000;
+pub const MAX_OI_CAP_STEP_BPS: u64 = 500; // Max OI cap change per update, as bps of pool liquidity
+pub const OI_CAP_UPDATE_INTERVAL_SECS: i64 = 60 * 60; // Min time between bounded OI cap updates
+pub const MAX_LEVERAGE: u64 = 50; // Ceiling for any market's max_leverage
+pub const MAX_MARGIN_TIERS: usize = 4;
+pub const MAX_ADL_KEEPERS: usize = 4;
+pub const MAX_MARKETS: u64 = 24; // Every market is priced for NAV, so keep the account list bounded
+
+// Conservative parameters for permissionless fair-launch listings
+pub const LISTING_MIN_SOL_LIQUIDITY: u64 = 50_000_000_000; // 50 SOL of real curve reserves
+pub const LISTING_TWAP_WINDOW_SECS: u64 = 30 * 60;
+pub const LISTING_MAX_LEVERAGE: u64 = 3;
+pub const LISTING_MAX_OI_BPS: u64 = 100; // 1% of pool liquidity
+pub const LISTING_MAX_PRICE_AGE_SECS: u64 = 60 * 60; // Curve must have traded within the hour
+pub const MAX_LISTED_MARKETS: u64 = 8; // Listings can never crowd out admin markets
+
 #[program]
 pub mod lp_pool {
     use super::*;
@@ -11,24 +28,537 @@ pub mod lp_pool {
     // --- Admin Functions ---
 
     pub fn initialize(ctx: Context<Initialize>, admin_fee_bps: u64) -> Result<()> {
+        require!(admin_fee_bps <= 10_000, CustomError::InvalidParameter);
+
         let pool = &mut ctx.accounts.pool;
         pool.admin = *ctx.accounts.admin.key;
         pool.total_liquidity = 0;
         pool.admin_fee_bps = admin_fee_bps; // e.g. 3000 for 30%
+        pool.collateral_mint = ctx.accounts.collateral_mint.key();
+        pool.token_account = ctx.accounts.pool_token_account.key();
+        pool.lp_mint = ctx.accounts.lp_mint.key();
+        pool.market_count = 0;
+        pool.listed_market_count = 0;
+        pool.protocol_fees = 0;
+        pool.insurance_fund = 0;
+        pool.insurance_vault = ctx.accounts.insurance_vault.key();
+        pool.insurance_fee_bps = 1000; // 10% of the LP share of fees
+        pool.insurance_liquidation_bps = 5000; // 50% of liquidation leftovers
+        pool.insurance_drawn = 0;
+        pool.uncovered_bad_debt = 0;
+        pool.adl_threshold_bps = 5000; // ADL once NAV drops below half of liquidity
+        pool.adl_keepers = [Pubkey::default(); MAX_ADL_KEEPERS];
+        pool.adl_keepers[0] = pool.admin;
+        pool.withdrawal_cooldown_secs = 24 * 60 * 60;
+        pool.withdrawal_epoch_secs = 24 * 60 * 60;
+        pool.max_epoch_withdrawal_bps = 1000; // 10% of NAV per epoch
+        pool.epoch_start = Clock::get()?.unix_timestamp;
+        pool.epoch_withdrawn = 0;
+        pool.authority_bump = *ctx.bumps.get("pool_authority").unwrap();
+        Ok(())
+    }
+
+    /// Sets the long and short OI caps. After the first setting, each cap may move by
+    /// at most `MAX_OI_CAP_STEP_BPS` of pool liquidity, at most once per
+    /// `OI_CAP_UPDATE_INTERVAL_SECS`, so a faulty monitor cannot swing the market's
+    /// risk quickly.
+    pub fn set_oi_caps(ctx: Context<UpdateMarket>, max_long_oi: u64, max_short_oi: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let max_step = bps_of(ctx.accounts.pool.total_liquidity, MAX_OI_CAP_STEP_BPS)?;
+        let market = &mut ctx.accounts.market;
+        // A new market's first caps are its initial sizing, not a step
+        if market.last_oi_cap_update != 0 {
+            require!(
+                now >= market.last_oi_cap_update.saturating_add(OI_CAP_UPDATE_INTERVAL_SECS),
+                CustomError::OiCapUpdateTooSoon
+            );
+            require!(
+                max_long_oi.abs_diff(market.max_long_oi) <= max_step
+                    && max_short_oi.abs_diff(market.max_short_oi) <= max_step,
+                CustomError::OiCapStepTooLarge
+            );
+        }
+        market.max_long_oi = max_long_oi;
+        market.max_short_oi = max_short_oi;
+        market.last_oi_cap_update = now;
+
+        msg!(
+            "Setting OI caps for {} to long {} / short {}",
+            market.symbol,
+            max_long_oi,
+            max_short_oi
+        );
         Ok(())
     }
 
-    pub fn set_max_oi(ctx: Context<SetMaxOI>, market_id: String, limit: u64) -> Result<()> {
+    pub fn create_market(
+        ctx: Context<CreateMarket>,
+        symbol: String,
+        oracle: Pubkey,
+        max_leverage: u64,
+        open_fee_bps: u64,
+        close_fee_bps: u64,
+        borrow_fee_bps_per_hour: u64,
+        maintenance_margin_bps: u64,
+        liquidation_fee_bps: u64,
+        status: MarketStatus,
+    ) -> Result<()> {
+        require!(
+            !symbol.is_empty() && symbol.len() <= MAX_SYMBOL_LEN,
+            CustomError::InvalidSymbol
+        );
+        require!(
+            (1..=MAX_LEVERAGE).contains(&max_leverage),
+            CustomError::MaxLeverageExceeded
+        );
+        require!(
+            open_fee_bps <= 1_000 && close_fee_bps <= 1_000 && borrow_fee_bps_per_hour <= 1_000,
+            CustomError::InvalidParameter
+        );
+        require!(
+            maintenance_margin_bps <= 10_000 && liquidation_fee_bps <= maintenance_margin_bps,
+            CustomError::InvalidParameter
+        );
+        require!(
+            ctx.accounts.pool.market_count < MAX_MARKETS,
+            CustomError::TooManyMarkets
+        );
+
+        let market = &mut ctx.accounts.market;
+        market.pool = ctx.accounts.pool.key();
+        market.symbol = symbol;
+        market.status = status;
+        market.oracle = oracle;
+        market.oracle_kind = OracleKind::Pyth;
+        market.twap_window_secs = 0;
+        market.max_leverage = max_leverage;
+        market.max_long_oi = 0; // Closed until the risk monitor sets limits
+        market.max_short_oi = 0;
+        market.last_oi_cap_update = 0;
+        market.long_oi = 0;
+        market.short_oi = 0;
+        market.long_avg_entry_price = 0;
+        market.short_avg_entry_price = 0;
+        market.open_fee_bps = open_fee_bps;   // e.g. 10 for 0.1% of size
+        market.close_fee_bps = close_fee_bps; // e.g. 10 for 0.1% of size
+        market.borrow_fee_bps_per_hour = borrow_fee_bps_per_hour; // e.g. 1 for 0.01%/h
+        market.funding_rate_bps_per_hour = 10; // 0.1%/h when fully one-sided
+        market.cumulative_funding_index = 0;
+        market.last_funding_time = Clock::get()?.unix_timestamp;
+        market.max_price_age_secs = 60;
+        market.max_confidence_bps = 200; // 2% of price
+        // Single tier until configured: full leverage at any size
+        let tier = MarginTier {
+            max_notional: u64::MAX,
+            initial_margin_bps: 10_000 / max_leverage,
+            maintenance_margin_bps, // e.g. 100 for 1%
+        };
+        require!(
+            maintenance_margin_bps <= tier.initial_margin_bps,
+            CustomError::InvalidParameter
+        );
+        market.set_margin_tiers(&[tier])?;
+        market.liquidation_fee_bps = liquidation_fee_bps; // e.g. 50 for 0.5%
+        market.skew_scale = 0; // No price impact until configured
+        market.impact_fee_balance = 0;
+        market.bump = *ctx.bumps.get("market").unwrap();
+
         let pool = &mut ctx.accounts.pool;
-        // In a real implementation, we would use a simpler mapping or PDA for market config
-        // For this demo, we verify admin and emitting event or mock storage
-        require!(ctx.accounts.admin.key() == pool.admin, CustomError::Unauthorized);
-        
-        msg!("Setting Max OI for {} to {}", market_id, limit);
-        // Store in a PDA or Map... (Simplified for file view)
+        pool.market_count = pool
+            .market_count
+            .checked_add(1)
+            .ok_or(CustomError::MathOverflow)?;
+
+        msg!("Market {} created", market.symbol);
+        Ok(())
+    }
+
+    /// Permissionless: lists a graduated fair-launch token as a perp market with
+    /// conservative risk parameters, priced from the curve's on-chain TWAP.
+    /// At most `MAX_LISTED_MARKETS` listings exist per pool, so they cannot grow the
+    /// NAV account list past what a transaction can carry.
+    /// Curves keep trading after graduation, but the TWAP goes stale
+    /// `LISTING_MAX_PRICE_AGE_SECS` after the last trade: trading then stops, NAV
+    /// counts only trader profits on the market, and once its open interest is gone
+    /// the admin can `delist_market` to free the slot.
+    pub fn list_graduated_token(ctx: Context<ListGraduatedToken>) -> Result<()> {
+        let curve = &ctx.accounts.curve_state;
+        require!(curve.graduated_at > 0, CustomError::NotGraduated);
+        require!(
+            curve.real_sol_res >= LISTING_MIN_SOL_LIQUIDITY,
+            CustomError::InsufficientLiquidity
+        );
+        require!(
+            ctx.accounts.pool.listed_market_count < MAX_LISTED_MARKETS
+                && ctx.accounts.pool.market_count < MAX_MARKETS,
+            CustomError::TooManyMarkets
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let observations = &ctx.accounts.observations;
+        require!(
+            (now as u64).saturating_sub(observations.last_update()) <= LISTING_MAX_PRICE_AGE_SECS,
+            CustomError::StaleOraclePrice
+        );
+        let twap = observations.twap(now as u64, LISTING_TWAP_WINDOW_SECS);
+        require!(
+            twap > 0 && i64::try_from(twap).is_ok(),
+            CustomError::InvalidOracle
+        );
+
+        let mint = curve.token_mint.to_string();
+        let pool = &mut ctx.accounts.pool;
+        let market = &mut ctx.accounts.market;
+        market.pool = pool.key();
+        market.symbol = format!("{}-PERP", &mint[..6]);
+        market.status = MarketStatus::Active;
+        market.oracle = observations.key();
+        market.oracle_kind = OracleKind::FairLaunchTwap;
+        market.twap_window_secs = LISTING_TWAP_WINDOW_SECS;
+        market.max_leverage = LISTING_MAX_LEVERAGE;
+        market.max_long_oi = bps_of(pool.total_liquidity, LISTING_MAX_OI_BPS)?;
+        market.max_short_oi = market.max_long_oi;
+        market.last_oi_cap_update = now;
+        market.long_oi = 0;
+        market.short_oi = 0;
+        market.long_avg_entry_price = 0;
+        market.short_avg_entry_price = 0;
+        market.open_fee_bps = 30;  // 0.3% of size
+        market.close_fee_bps = 30; // 0.3% of size
+        market.borrow_fee_bps_per_hour = 5; // 0.05% of size per hour
+        market.funding_rate_bps_per_hour = 25; // 0.25%/h when fully one-sided
+        market.cumulative_funding_index = 0;
+        market.last_funding_time = now;
+        market.max_price_age_secs = LISTING_MAX_PRICE_AGE_SECS; // Since the curve's last trade
+        market.max_confidence_bps = 500; // Max spot/TWAP divergence
+        // Leverage shrinks as positions grow relative to the market's OI cap
+        let cap = market.max_long_oi;
+        require!(cap >= 4, CustomError::InsufficientLiquidity);
+        market.set_margin_tiers(&[
+            MarginTier { max_notional: cap / 4, initial_margin_bps: 3333, maintenance_margin_bps: 1000 },
+            MarginTier { max_notional: cap / 2, initial_margin_bps: 5000, maintenance_margin_bps: 1500 },
+            MarginTier { max_notional: u64::MAX, initial_margin_bps: 10_000, maintenance_margin_bps: 2500 },
+        ])?;
+        market.liquidation_fee_bps = 250; // 2.5%
+        market.skew_scale = 0;
+        market.impact_fee_balance = 0;
+        market.bump = *ctx.bumps.get("market").unwrap();
+
+        pool.market_count = pool
+            .market_count
+            .checked_add(1)
+            .ok_or(CustomError::MathOverflow)?;
+        pool.listed_market_count = pool
+            .listed_market_count
+            .checked_add(1)
+            .ok_or(CustomError::MathOverflow)?;
+
+        emit!(MarketListed {
+            market: market.key(),
+            token_mint: curve.token_mint,
+            symbol: market.symbol.clone(),
+            twap,
+            max_long_oi: market.max_long_oi,
+            max_short_oi: market.max_short_oi,
+            lister: ctx.accounts.payer.key(),
+        });
+
+        msg!("Market {} listed at TWAP {}", market.symbol, twap);
+        Ok(())
+    }
+
+    /// Removes a market without open interest from the pool, freeing its NAV slot (and
+    /// listing slot). The account is closed, so a token can be listed again later.
+    pub fn delist_market(ctx: Context<DelistMarket>) -> Result<()> {
+        let market = &ctx.accounts.market;
+        require!(
+            market.long_oi == 0 && market.short_oi == 0,
+            CustomError::MarketHasOpenInterest
+        );
+
+        let pool = &mut ctx.accounts.pool;
+        pool.market_count = pool
+            .market_count
+            .checked_sub(1)
+            .ok_or(CustomError::MathOverflow)?;
+        if market.oracle_kind == OracleKind::FairLaunchTwap {
+            pool.listed_market_count = pool
+                .listed_market_count
+                .checked_sub(1)
+                .ok_or(CustomError::MathOverflow)?;
+        }
+
+        emit!(MarketDelisted {
+            market: market.key(),
+            symbol: market.symbol.clone(),
+        });
+
+        msg!("Market {} delisted", market.symbol);
+        Ok(())
+    }
+
+    pub fn set_market_status(ctx: Context<UpdateMarket>, status: MarketStatus) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        market.status = status;
+
+        msg!("Market {} status: {:?}", market.symbol, status);
+        Ok(())
+    }
+
+    pub fn set_liquidation_fee(ctx: Context<UpdateMarket>, liquidation_fee_bps: u64) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        // The bounty must fit inside the smallest maintenance margin
+        require!(
+            liquidation_fee_bps <= market.margin_tiers[0].maintenance_margin_bps,
+            CustomError::InvalidParameter
+        );
+        market.liquidation_fee_bps = liquidation_fee_bps;
+
+        msg!("Liquidation fee for {}: {} bps", market.symbol, liquidation_fee_bps);
+        Ok(())
+    }
+
+    /// Sets the market's max leverage and its margin tiers. Tiers are ordered by
+    /// notional; margins may only grow with size and no tier may allow more than
+    /// `max_leverage`. The last tier applies to any larger position.
+    pub fn set_margin_params(
+        ctx: Context<UpdateMarket>,
+        max_leverage: u64,
+        tiers: Vec<MarginTier>,
+    ) -> Result<()> {
+        require!(
+            (1..=MAX_LEVERAGE).contains(&max_leverage),
+            CustomError::MaxLeverageExceeded
+        );
+        let market = &mut ctx.accounts.market;
+        require!(
+            tiers
+                .iter()
+                .all(|tier| tier.initial_margin_bps >= 10_000 / max_leverage),
+            CustomError::InvalidMarginTiers
+        );
+        market.set_margin_tiers(&tiers)?;
+        require!(
+            market.liquidation_fee_bps <= market.margin_tiers[0].maintenance_margin_bps,
+            CustomError::InvalidMarginTiers
+        );
+        market.max_leverage = max_leverage;
+
+        msg!(
+            "Margin params for {}: max {}x, {} tiers",
+            market.symbol,
+            max_leverage,
+            tiers.len()
+        );
+        Ok(())
+    }
+
+    pub fn set_oracle_params(
+        ctx: Context<UpdateMarket>,
+        oracle: Pubkey,
+        max_price_age_secs: u64,
+        max_confidence_bps: u64,
+    ) -> Result<()> {
+        require!(
+            max_price_age_secs > 0 && max_confidence_bps <= 10_000,
+            CustomError::InvalidParameter
+        );
+
+        let market = &mut ctx.accounts.market;
+        market.oracle = oracle;
+        market.max_price_age_secs = max_price_age_secs;
+        market.max_confidence_bps = max_confidence_bps;
+
+        msg!(
+            "Oracle for {}: {} (max age {}s, max conf {} bps)",
+            market.symbol,
+            oracle,
+            max_price_age_secs,
+            max_confidence_bps
+        );
+        Ok(())
+    }
+
+    pub fn set_funding_rate(ctx: Context<UpdateMarket>, funding_rate_bps_per_hour: u64) -> Result<()> {
+        require!(funding_rate_bps_per_hour <= 10_000, CustomError::InvalidParameter);
+
+        // Accrue at the old rate before switching
+        let market_key = ctx.accounts.market.key();
+        let market = &mut ctx.accounts.market;
+        market.update_funding(market_key, Clock::get()?.unix_timestamp)?;
+        market.funding_rate_bps_per_hour = funding_rate_bps_per_hour;
+
+        msg!("Funding rate for {}: {} bps/h", market.symbol, funding_rate_bps_per_hour);
+        Ok(())
+    }
+
+    pub fn set_price_impact(ctx: Context<UpdateMarket>, skew_scale: u64) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        market.skew_scale = skew_scale;
+
+        msg!("Price impact for {}: skew scale {}", market.symbol, skew_scale);
+        Ok(())
+    }
+
+    pub fn set_market_fees(
+        ctx: Context<UpdateMarket>,
+        open_fee_bps: u64,
+        close_fee_bps: u64,
+        borrow_fee_bps_per_hour: u64,
+    ) -> Result<()> {
+        require!(
+            open_fee_bps <= 1_000 && close_fee_bps <= 1_000 && borrow_fee_bps_per_hour <= 1_000,
+            CustomError::InvalidParameter
+        );
+
+        let market = &mut ctx.accounts.market;
+        market.open_fee_bps = open_fee_bps;
+        market.close_fee_bps = close_fee_bps;
+        market.borrow_fee_bps_per_hour = borrow_fee_bps_per_hour;
+
+        msg!(
+            "Fees for {}: open {} bps, close {} bps, borrow {} bps/h",
+            market.symbol,
+            open_fee_bps,
+            close_fee_bps,
+            borrow_fee_bps_per_hour
+        );
+        Ok(())
+    }
+
+    pub fn claim_protocol_fees(ctx: Context<ClaimProtocolFees>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let amount = pool.protocol_fees;
+        require!(amount > 0, CustomError::InvalidAmount);
+        pool.protocol_fees = 0;
+
+        transfer_from_pool(
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool_token_account,
+            &ctx.accounts.admin_token_account,
+            &ctx.accounts.pool_authority,
+            pool,
+            amount,
+        )?;
+
+        msg!("Protocol fees claimed: {}", amount);
+        Ok(())
+    }
+
+    pub fn set_insurance_params(
+        ctx: Context<UpdatePool>,
+        insurance_fee_bps: u64,
+        insurance_liquidation_bps: u64,
+    ) -> Result<()> {
+        require!(
+            insurance_fee_bps <= 10_000 && insurance_liquidation_bps <= 10_000,
+            CustomError::InvalidParameter
+        );
+
+        let pool = &mut ctx.accounts.pool;
+        pool.insurance_fee_bps = insurance_fee_bps;
+        pool.insurance_liquidation_bps = insurance_liquidation_bps;
+
+        msg!(
+            "Insurance params: {} bps of fees, {} bps of liquidations",
+            insurance_fee_bps,
+            insurance_liquidation_bps
+        );
+        Ok(())
+    }
+
+    pub fn set_adl_threshold(ctx: Context<UpdatePool>, adl_threshold_bps: u64) -> Result<()> {
+        require!(adl_threshold_bps <= 10_000, CustomError::InvalidParameter);
+        ctx.accounts.pool.adl_threshold_bps = adl_threshold_bps;
+
+        msg!("ADL threshold: NAV below {} bps of liquidity", adl_threshold_bps);
+        Ok(())
+    }
+
+    /// Replaces the keepers allowed to run `auto_deleverage`
+    pub fn set_adl_keepers(ctx: Context<UpdatePool>, keepers: Vec<Pubkey>) -> Result<()> {
+        require!(keepers.len() <= MAX_ADL_KEEPERS, CustomError::InvalidParameter);
+        let pool = &mut ctx.accounts.pool;
+        pool.adl_keepers = [Pubkey::default(); MAX_ADL_KEEPERS];
+        pool.adl_keepers[..keepers.len()].copy_from_slice(&keepers);
+
+        msg!("ADL keepers updated: {}", keepers.len());
+        Ok(())
+    }
+
+    pub fn set_withdrawal_params(
+        ctx: Context<UpdatePool>,
+        withdrawal_cooldown_secs: u64,
+        withdrawal_epoch_secs: u64,
+        max_epoch_withdrawal_bps: u64,
+    ) -> Result<()> {
+        require!(
+            withdrawal_epoch_secs > 0 && max_epoch_withdrawal_bps > 0 && max_epoch_withdrawal_bps <= 10_000,
+            CustomError::InvalidParameter
+        );
+
+        let pool = &mut ctx.accounts.pool;
+        pool.withdrawal_cooldown_secs = withdrawal_cooldown_secs;
+        pool.withdrawal_epoch_secs = withdrawal_epoch_secs;
+        pool.max_epoch_withdrawal_bps = max_epoch_withdrawal_bps;
+
+        msg!(
+            "Withdrawals: {}s cooldown, {} bps of NAV per {}s epoch",
+            withdrawal_cooldown_secs,
+            max_epoch_withdrawal_bps,
+            withdrawal_epoch_secs
+        );
+        Ok(())
+    }
+
+    // --- Insurance Fund ---
+
+    /// Anyone may top up the insurance fund directly
+    pub fn fund_insurance(ctx: Context<FundInsurance>, amount: u64) -> Result<()> {
+        require!(amount > 0, CustomError::InvalidAmount);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_token_account.to_account_info(),
+            to: ctx.accounts.insurance_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, amount)?;
+
+        let pool = &mut ctx.accounts.pool;
+        pool.insurance_fund = pool
+            .insurance_fund
+            .checked_add(amount)
+            .ok_or(CustomError::MathOverflow)?;
+
+        msg!("Insurance fund topped up: {}", amount);
         Ok(())
     }
 
+    pub fn get_insurance_fund(ctx: Context<GetPoolNav>) -> Result<InsuranceFundHealth> {
+        let pool = &ctx.accounts.pool;
+        let coverage_bps = if pool.total_liquidity == 0 {
+            0
+        } else {
+            pro_rata(pool.insurance_fund, 10_000, pool.total_liquidity)?
+        };
+        let health = InsuranceFundHealth {
+            balance: pool.insurance_fund,
+            coverage_bps,
+            total_drawn: pool.insurance_drawn,
+            uncovered_bad_debt: pool.uncovered_bad_debt,
+        };
+
+        msg!(
+            "Insurance Fund: {} ({} bps of liquidity), drawn {}, uncovered {}",
+            health.balance,
+            health.coverage_bps,
+            health.total_drawn,
+            health.uncovered_bad_debt
+        );
+        Ok(health)
+    }
+
     // --- LP Functions ---
 
     pub fn add_liquidity(ctx: Context<AddLiquidity>, amount: u64) -> Result<()> {
@@ -42,37 +572,193 @@ pub mod lp_pool {
         
         token::transfer(cpi_ctx, amount)?;
 
+        // Mint LP shares priced from pool NAV before the deposit
+        let nav = pool_nav(&ctx.accounts.pool, ctx.remaining_accounts)?;
+        let shares = shares_for_deposit(amount, ctx.accounts.lp_mint.supply, nav)?;
+        require!(shares > 0, CustomError::InvalidAmount);
+
+        let pool_key = ctx.accounts.pool.key();
+        let seeds = &[b"pool_authority", pool_key.as_ref(), &[ctx.accounts.pool.authority_bump]];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.lp_mint.to_account_info(),
+            to: ctx.accounts.user_lp_account.to_account_info(),
+            authority: ctx.accounts.pool_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token::mint_to(CpiContext::new_with_signer(cpi_program, cpi_accounts, signer), shares)?;
+
         let pool = &mut ctx.accounts.pool;
         pool.total_liquidity += amount;
+
+        emit!(LiquidityAdded {
+            provider: ctx.accounts.user.key(),
+            amount,
+            shares,
+            nav,
+        });
         
-        msg!("Liquidity Added: {}", amount);
+        msg!("Liquidity Added: {} for {} shares", amount, shares);
+        Ok(())
+    }
+
+    /// Queues a withdrawal: shares are locked in escrow now and redeemed at NAV
+    /// once the cooldown has passed
+    pub fn request_withdrawal(ctx: Context<RequestWithdrawal>, shares: u64) -> Result<()> {
+        require!(shares > 0, CustomError::InvalidAmount);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_lp_account.to_account_info(),
+            to: ctx.accounts.withdrawal_escrow.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, shares)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let request = &mut ctx.accounts.withdrawal_request;
+        request.owner = ctx.accounts.user.key();
+        request.pool = ctx.accounts.pool.key();
+        request.shares = shares;
+        request.requested_at = now;
+        request.unlock_at = now
+            .checked_add(ctx.accounts.pool.withdrawal_cooldown_secs as i64)
+            .ok_or(CustomError::MathOverflow)?;
+        request.bump = *ctx.bumps.get("withdrawal_request").unwrap();
+
+        emit!(WithdrawalRequested {
+            provider: request.owner,
+            shares,
+            unlock_at: request.unlock_at,
+        });
+
+        msg!("Withdrawal Requested: {} shares, unlocks at {}", shares, request.unlock_at);
+        Ok(())
+    }
+
+    /// Redeems a matured request at current NAV. Anyone may process the queue; the
+    /// per-epoch cap may fill a request only partially, leaving the rest queued.
+    pub fn execute_withdrawal(ctx: Context<ExecuteWithdrawal>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now >= ctx.accounts.withdrawal_request.unlock_at,
+            CustomError::WithdrawalLocked
+        );
+
+        // 1. Roll the epoch and work out what is left of its cap
+        let nav = pool_nav(&ctx.accounts.pool, ctx.remaining_accounts)?;
+        let pool = &mut ctx.accounts.pool;
+        let available = pool.epoch_allowance(nav, now)?;
+
+        // 2. Shares redeem for their pro-rata slice of NAV, up to the remaining cap
+        let requested = ctx.accounts.withdrawal_request.shares;
+        let (shares, amount) = withdrawal_fill(requested, ctx.accounts.lp_mint.supply, nav, available)?;
+        require!(amount <= pool.total_liquidity, CustomError::InsufficientLiquidity);
+
+        // The final fill also burns anything sent to the escrow on top, so it can close
+        let burn = if shares == requested {
+            ctx.accounts.withdrawal_escrow.amount
+        } else {
+            shares
+        };
+        let pool_key = pool.key();
+        let seeds = &[b"pool_authority", pool_key.as_ref(), &[pool.authority_bump]];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.lp_mint.to_account_info(),
+            from: ctx.accounts.withdrawal_escrow.to_account_info(),
+            authority: ctx.accounts.pool_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token::burn(CpiContext::new_with_signer(cpi_program, cpi_accounts, signer), burn)?;
+
+        pool.total_liquidity -= amount;
+        pool.epoch_withdrawn = pool
+            .epoch_withdrawn
+            .checked_add(amount)
+            .ok_or(CustomError::MathOverflow)?;
+
+        if amount > 0 {
+            transfer_from_pool(
+                &ctx.accounts.token_program,
+                &ctx.accounts.pool_token_account,
+                &ctx.accounts.owner_token_account,
+                &ctx.accounts.pool_authority,
+                pool,
+                amount,
+            )?;
+        }
+
+        // 3. Fully processed requests release their accounts back to the owner
+        let request = &mut ctx.accounts.withdrawal_request;
+        request.shares -= shares;
+        if request.shares == 0 {
+            close_pool_token_account(
+                &ctx.accounts.token_program,
+                &ctx.accounts.withdrawal_escrow,
+                &ctx.accounts.owner,
+                &ctx.accounts.pool_authority,
+                pool,
+            )?;
+            request.close(ctx.accounts.owner.to_account_info())?;
+        }
+
+        emit!(LiquidityRemoved {
+            provider: request.owner,
+            amount,
+            shares,
+            nav,
+        });
+
+        msg!("Liquidity Removed: {} for {} shares ({} queued)", amount, shares, request.shares);
+        Ok(())
+    }
+
+    pub fn cancel_withdrawal(ctx: Context<CancelWithdrawal>) -> Result<()> {
+        // Whole escrow balance, so stray deposits cannot block the close
+        let shares = ctx.accounts.withdrawal_escrow.amount;
+        transfer_from_pool(
+            &ctx.accounts.token_program,
+            &ctx.accounts.withdrawal_escrow,
+            &ctx.accounts.user_lp_account,
+            &ctx.accounts.pool_authority,
+            &ctx.accounts.pool,
+            shares,
+        )?;
+        close_pool_token_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.withdrawal_escrow,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.pool_authority,
+            &ctx.accounts.pool,
+        )?;
+
+        msg!("Withdrawal Cancelled: {} shares returned", shares);
         Ok(())
     }
 
+    /// View: pool NAV including traders' unrealized PnL. Remaining accounts must be
+    /// every market of the pool followed by its oracle, as (market, price_feed) pairs.
+    pub fn get_pool_nav(ctx: Context<GetPoolNav>) -> Result<u64> {
+        let nav = pool_nav(&ctx.accounts.pool, ctx.remaining_accounts)?;
+        msg!("Pool NAV: {}", nav);
+        Ok(nav)
+    }
+
     // --- Trading Functions ---
 
     pub fn open_position(
         ctx: Context<OpenPosition>, 
-        market_id: String, 
         is_long: bool, 
         collateral: u64, 
         leverage: u64
     ) -> Result<()> {
-        require!(leverage <= 50, CustomError::MaxLeverageExceeded);
+        // 1. Check Pyth Price (bound, fresh and confident)
+        let current_price = load_oracle_price(&ctx.accounts.price_feed, &ctx.accounts.market)?;
         
-        // 1. Check Pyth Price
-        let price_account_info = &ctx.accounts.price_feed;
-        let price_feed = load_price_feed_from_account_info(price_account_info).unwrap();
-        let current_price = price_feed.get_price_unchecked(); // Verify staleness in prod
-        
-        msg!("Current Price: {}", current_price.price);
-
-        // 2. Golden Ratio Check (Crucial)
-        // Check if New OI > 50% of Liquidity
-        // Fetch Max OI from config PDA (omitted for brevity)
-        // require!(new_oi <= max_oi, CustomError::GoldenRatioBreached);
+        msg!("Current Price: {}", current_price);
 
-        // 3. Transfer Collateral
+        // 2. Transfer Collateral
         let cpi_accounts = Transfer {
             from: ctx.accounts.user_token_account.to_account_info(),
             to: ctx.accounts.pool_token_account.to_account_info(),
@@ -82,97 +768,3076 @@ pub mod lp_pool {
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
         token::transfer(cpi_ctx, collateral)?;
 
-        // 4. Record Position
+        // 3. Record Position (fresh, or increase the existing one for this side)
+        let insurance_before = ctx.accounts.pool.insurance_fund;
         let position = &mut ctx.accounts.position;
         position.trader = *ctx.accounts.user.key;
-        position.market_id = market_id;
+        position.market = ctx.accounts.market.key();
         position.is_long = is_long;
-        position.size = collateral * leverage;
-        position.collateral = collateral;
-        position.entry_price = current_price.price;
-        position.timestamp = Clock::get()?.unix_timestamp;
+        position.bump = *ctx.bumps.get("position").unwrap();
+        apply_open(
+            &mut ctx.accounts.pool,
+            &mut ctx.accounts.market,
+            position,
+            collateral,
+            leverage,
+            current_price,
+            Clock::get()?.unix_timestamp,
+        )?;
+        sync_insurance_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool_token_account,
+            &ctx.accounts.insurance_vault,
+            &ctx.accounts.pool_authority,
+            &ctx.accounts.pool,
+            insurance_before,
+        )?;
 
         msg!("Position Opened: Size ${}", position.size);
         Ok(())
     }
-}
 
-// --- Account Structs ---
+    pub fn place_limit_order(
+        ctx: Context<PlaceLimitOrder>,
+        order_id: u64,
+        is_long: bool,
+        collateral: u64,
+        leverage: u64,
+        limit_price: i64,
+        expiry: i64,
+        keeper_fee: u64,
+    ) -> Result<()> {
+        require!(collateral > 0, CustomError::InvalidAmount);
+        require!(limit_price > 0, CustomError::InvalidParameter);
+        // Reject orders that could not fill today (leverage, margin tier, OI caps).
+        // Checked standalone; a fill merging into an existing position is rechecked.
+        ctx.accounts.market.validate_open(
+            ctx.accounts.pool.total_liquidity,
+            is_long,
+            0,
+            0,
+            collateral,
+            leverage,
+        )?;
+        require!(
+            expiry > Clock::get()?.unix_timestamp,
+            CustomError::InvalidParameter
+        );
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(init, payer = admin, space = 8 + 32 + 8 + 8)]
-    pub pool: Account<'info, PoolState>,
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+        // Collateral waits in the order's own escrow until a keeper fills it
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_token_account.to_account_info(),
+            to: ctx.accounts.order_escrow.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, collateral)?;
 
-#[derive(Accounts)]
-pub struct AddLiquidity<'info> {
-    #[account(mut)]
-    pub pool: Account<'info, PoolState>,
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub pool_token_account: Account<'info, TokenAccount>,
-    pub user: Signer<'info>,
-    pub token_program: Program<'info, Token>,
-}
+        // Keeper fee is escrowed in the order account as lamports
+        if keeper_fee > 0 {
+            anchor_lang::solana_program::program::invoke(
+                &anchor_lang::solana_program::system_instruction::transfer(
+                    &ctx.accounts.user.key(),
+                    &ctx.accounts.limit_order.key(),
+                    keeper_fee,
+                ),
+                &[
+                    ctx.accounts.user.to_account_info(),
+                    ctx.accounts.limit_order.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+        }
 
-#[derive(Accounts)]
-pub struct OpenPosition<'info> {
-    #[account(mut)]
-    pub pool: Account<'info, PoolState>,
-    #[account(init, payer = user, space = 8 + 200)] // Adjust space
-    pub position: Account<'info, PositionState>,
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub pool_token_account: Account<'info, TokenAccount>,
-    /// CHECK: Pyth Price Feed Account
-    pub price_feed: AccountInfo<'info>, 
-    #[account(mut)]
-    pub user: Signer<'info>,
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-}
+        let order = &mut ctx.accounts.limit_order;
+        order.trader = ctx.accounts.user.key();
+        order.market = ctx.accounts.market.key();
+        order.order_id = order_id;
+        order.is_long = is_long;
+        order.collateral = collateral;
+        order.leverage = leverage;
+        order.limit_price = limit_price;
+        order.expiry = expiry;
+        order.keeper_fee = keeper_fee;
+        order.bump = *ctx.bumps.get("limit_order").unwrap();
 
-#[derive(Accounts)]
-pub struct SetMaxOI<'info> {
-    #[account(mut)]
-    pub pool: Account<'info, PoolState>,
-    pub admin: Signer<'info>,
-}
+        msg!("Limit Order Placed: {} x{} at {}", collateral, leverage, limit_price);
+        Ok(())
+    }
 
-// --- Data Structures ---
+    pub fn cancel_limit_order(ctx: Context<CancelLimitOrder>) -> Result<()> {
+        // Full refund: collateral here, escrow rent below, keeper fee and order rent via close
+        let collateral = ctx.accounts.limit_order.collateral;
+        transfer_from_pool(
+            &ctx.accounts.token_program,
+            &ctx.accounts.order_escrow,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.pool_authority,
+            &ctx.accounts.pool,
+            collateral,
+        )?;
+        close_pool_token_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.order_escrow,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.pool_authority,
+            &ctx.accounts.pool,
+        )?;
 
-#[account]
-pub struct PoolState {
-    pub admin: Pubkey,
-    pub total_liquidity: u64,
-    pub admin_fee_bps: u64,
-}
+        msg!("Limit Order Cancelled: refunded {}", collateral);
+        Ok(())
+    }
 
-#[account]
-pub struct PositionState {
-    pub trader: Pubkey,
-    pub market_id: String, // "SOL-USD"
-    pub is_long: bool,
-    pub size: u64,
-    pub collateral: u64,
-    pub entry_price: i64,
-    pub timestamp: i64,
-}
+    pub fn close_position(ctx: Context<ClosePosition>) -> Result<()> {
+        let exit_price = load_oracle_price(&ctx.accounts.price_feed, &ctx.accounts.market)?;
 
-// --- Errors ---
+        let now = Clock::get()?.unix_timestamp;
+        let pool = &mut ctx.accounts.pool;
+        let insurance_before = pool.insurance_fund;
+        let market = &mut ctx.accounts.market;
+        let position = &mut ctx.accounts.position;
+        market.update_funding(position.market, now)?;
 
-#[error_code]
-pub enum CustomError {
-    #[msg("Unauthorized access")]
-    Unauthorized,
-    #[msg("Max leverage exceeded (Max 50x)")]
-    MaxLeverageExceeded,
-    #[msg("Golden Ratio Breached: Max OI Reached")]
-    GoldenRatioBreached,
+        // Profits are paid from LP liquidity, losses (and funding paid) are absorbed into it
+        let size = position.size;
+        let settlement = reduce_position(pool, market, position, size, exit_price, now, true)?;
+        sync_insurance_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool_token_account,
+            &ctx.accounts.insurance_vault,
+            &ctx.accounts.pool_authority,
+            pool,
+            insurance_before,
+        )?;
+
+        if settlement.payout > 0 {
+            transfer_from_pool(
+                &ctx.accounts.token_program,
+                &ctx.accounts.pool_token_account,
+                &ctx.accounts.user_token_account,
+                &ctx.accounts.pool_authority,
+                pool,
+                settlement.payout,
+            )?;
+        }
+
+        emit!(PositionClosed {
+            position: position.key(),
+            trader: position.trader,
+            market: position.market,
+            is_long: position.is_long,
+            size,
+            entry_price: position.entry_price,
+            exit_price,
+            pnl: settlement.pnl,
+            funding: settlement.funding,
+            fees: settlement.fees,
+            payout: settlement.payout,
+        });
+
+        msg!(
+            "Position Closed: PnL {} Funding {} Fees {} Payout {}",
+            settlement.pnl,
+            settlement.funding,
+            settlement.fees,
+            settlement.payout
+        );
+        Ok(())
+    }
+
+    pub fn add_collateral(ctx: Context<ModifyPosition>, amount: u64) -> Result<()> {
+        require!(amount > 0, CustomError::InvalidAmount);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_token_account.to_account_info(),
+            to: ctx.accounts.pool_token_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token::transfer(CpiContext::new(cpi_program, cpi_accounts), amount)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let insurance_before = ctx.accounts.pool.insurance_fund;
+        let market = &mut ctx.accounts.market;
+        let position = &mut ctx.accounts.position;
+        market.update_funding(position.market, now)?;
+        settle_accruals(&mut ctx.accounts.pool, market, position, now)?;
+        sync_insurance_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool_token_account,
+            &ctx.accounts.insurance_vault,
+            &ctx.accounts.pool_authority,
+            &ctx.accounts.pool,
+            insurance_before,
+        )?;
+        position.collateral = position
+            .collateral
+            .checked_add(amount)
+            .ok_or(CustomError::MathOverflow)?;
+
+        emit!(CollateralAdded {
+            position: position.key(),
+            trader: position.trader,
+            amount,
+            collateral: position.collateral,
+        });
+
+        msg!("Collateral Added: {} (now {})", amount, position.collateral);
+        Ok(())
+    }
+
+    pub fn remove_collateral(ctx: Context<ModifyPosition>, amount: u64) -> Result<()> {
+        require!(amount > 0, CustomError::InvalidAmount);
+        let price = load_oracle_price(&ctx.accounts.price_feed, &ctx.accounts.market)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let pool = &mut ctx.accounts.pool;
+        let insurance_before = pool.insurance_fund;
+        let market = &mut ctx.accounts.market;
+        let position = &mut ctx.accounts.position;
+        market.update_funding(position.market, now)?;
+        settle_accruals(pool, market, position, now)?;
+        sync_insurance_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool_token_account,
+            &ctx.accounts.insurance_vault,
+            &ctx.accounts.pool_authority,
+            pool,
+            insurance_before,
+        )?;
+
+        require!(amount < position.collateral, CustomError::InsufficientCollateral);
+        position.collateral -= amount;
+
+        // Initial margin at the current price; unrealized profit can't back a withdrawal
+        let pnl = position_pnl(position.is_long, position.size, position.entry_price, price)?;
+        let equity = (position.collateral as i128) + (pnl.min(0) as i128);
+        let initial_margin = market.initial_margin(position.size)?;
+        require!(
+            equity >= initial_margin as i128,
+            CustomError::InsufficientMargin
+        );
+
+        transfer_from_pool(
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool_token_account,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.pool_authority,
+            pool,
+            amount,
+        )?;
+
+        emit!(CollateralRemoved {
+            position: position.key(),
+            trader: position.trader,
+            amount,
+            collateral: position.collateral,
+        });
+
+        msg!("Collateral Removed: {} (now {})", amount, position.collateral);
+        Ok(())
+    }
+
+    pub fn decrease_position(ctx: Context<ModifyPosition>, size_delta: u64) -> Result<()> {
+        let price = load_oracle_price(&ctx.accounts.price_feed, &ctx.accounts.market)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let pool = &mut ctx.accounts.pool;
+        let insurance_before = pool.insurance_fund;
+        let market = &mut ctx.accounts.market;
+        let position = &mut ctx.accounts.position;
+        // Full exits go through close_position
+        require!(
+            size_delta > 0 && size_delta < position.size,
+            CustomError::InvalidAmount
+        );
+        market.update_funding(position.market, now)?;
+
+        // 1. Realize PnL on the closed portion against a pro-rata slice of collateral
+        let settlement = reduce_position(pool, market, position, size_delta, price, now, true)?;
+
+        // 2. What remains must still be above maintenance margin
+        market.check_maintenance(position, price)?;
+        sync_insurance_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool_token_account,
+            &ctx.accounts.insurance_vault,
+            &ctx.accounts.pool_authority,
+            pool,
+            insurance_before,
+        )?;
+
+        if settlement.payout > 0 {
+            transfer_from_pool(
+                &ctx.accounts.token_program,
+                &ctx.accounts.pool_token_account,
+                &ctx.accounts.user_token_account,
+                &ctx.accounts.pool_authority,
+                pool,
+                settlement.payout,
+            )?;
+        }
+
+        emit!(PositionDecreased {
+            position: position.key(),
+            trader: position.trader,
+            market: position.market,
+            is_long: position.is_long,
+            size_delta,
+            remaining_size: position.size,
+            price,
+            pnl: settlement.pnl,
+            fees: settlement.fees,
+            payout: settlement.payout,
+        });
+
+        msg!(
+            "Position Decreased: -{} PnL {} Payout {}",
+            size_delta,
+            settlement.pnl,
+            settlement.payout
+        );
+        Ok(())
+    }
+
+    pub fn place_trigger_order(
+        ctx: Context<PlaceTriggerOrder>,
+        kind: TriggerKind,
+        trigger_price: i64,
+        size: u64,
+        keeper_fee: u64,
+    ) -> Result<()> {
+        require!(trigger_price > 0, CustomError::InvalidParameter);
+
+        // Escrow the keeper fee in the order account itself
+        if keeper_fee > 0 {
+            anchor_lang::solana_program::program::invoke(
+                &anchor_lang::solana_program::system_instruction::transfer(
+                    &ctx.accounts.user.key(),
+                    &ctx.accounts.trigger_order.key(),
+                    keeper_fee,
+                ),
+                &[
+                    ctx.accounts.user.to_account_info(),
+                    ctx.accounts.trigger_order.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+        }
+
+        let position = &ctx.accounts.position;
+        let order = &mut ctx.accounts.trigger_order;
+        order.position = position.key();
+        order.trader = position.trader;
+        order.position_opened_at = position.timestamp;
+        order.kind = kind;
+        order.trigger_price = trigger_price;
+        order.size = size; // 0 = whole position
+        order.keeper_fee = keeper_fee;
+        order.bump = *ctx.bumps.get("trigger_order").unwrap();
+
+        msg!("Trigger Order Placed: {:?} at {}", kind, trigger_price);
+        Ok(())
+    }
+
+    pub fn cancel_trigger_order(_ctx: Context<CancelTriggerOrder>) -> Result<()> {
+        msg!("Trigger Order Cancelled");
+        Ok(())
+    }
+
+    // --- Keeper Functions ---
+
+    pub fn execute_trigger_order(ctx: Context<ExecuteTriggerOrder>) -> Result<()> {
+        let price = load_oracle_price(&ctx.accounts.price_feed, &ctx.accounts.market)?;
+
+        let order = &ctx.accounts.trigger_order;
+        let position = &mut ctx.accounts.position;
+        // An order placed on an earlier position in the same PDA is stale
+        require!(
+            position.size > 0 && order.position_opened_at == position.timestamp,
+            CustomError::StaleTriggerOrder
+        );
+        require!(
+            trigger_crossed(order.kind, position.is_long, order.trigger_price, price),
+            CustomError::TriggerNotReached
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let pool = &mut ctx.accounts.pool;
+        let insurance_before = pool.insurance_fund;
+        let market_key = ctx.accounts.market.key();
+        let market = &mut ctx.accounts.market;
+        market.update_funding(market_key, now)?;
+
+        let size_delta = if order.size == 0 { position.size } else { order.size.min(position.size) };
+        let settlement = reduce_position(pool, market, position, size_delta, price, now, true)?;
+        // A partial fill must leave the rest above maintenance margin, as with a manual decrease
+        market.check_maintenance(position, price)?;
+        sync_insurance_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool_token_account,
+            &ctx.accounts.insurance_vault,
+            &ctx.accounts.pool_authority,
+            pool,
+            insurance_before,
+        )?;
+
+        if settlement.payout > 0 {
+            transfer_from_pool(
+                &ctx.accounts.token_program,
+                &ctx.accounts.pool_token_account,
+                &ctx.accounts.trader_token_account,
+                &ctx.accounts.pool_authority,
+                pool,
+                settlement.payout,
+            )?;
+        }
+
+        emit!(TriggerOrderExecuted {
+            order: order.key(),
+            position: position.key(),
+            trader: position.trader,
+            keeper: ctx.accounts.keeper.key(),
+            kind: order.kind,
+            trigger_price: order.trigger_price,
+            price,
+            size_delta,
+            pnl: settlement.pnl,
+            payout: settlement.payout,
+            keeper_fee: order.keeper_fee,
+        });
+
+        // Fully closed: return the position rent to the trader
+        if position.size == 0 {
+            position.close(ctx.accounts.trader.to_account_info())?;
+        }
+
+        msg!("Trigger Order Executed: -{} at {}", size_delta, price);
+        Ok(())
+    }
+
+    pub fn execute_limit_order(ctx: Context<ExecuteLimitOrder>) -> Result<()> {
+        let price = load_oracle_price(&ctx.accounts.price_feed, &ctx.accounts.market)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let order = &ctx.accounts.limit_order;
+        require!(now <= order.expiry, CustomError::LimitOrderExpired);
+        // Longs fill at or below the limit, shorts at or above
+        let reached = if order.is_long {
+            price <= order.limit_price
+        } else {
+            price >= order.limit_price
+        };
+        require!(reached, CustomError::TriggerNotReached);
+
+        // Move the escrowed collateral into the pool vault and release the escrow
+        transfer_from_pool(
+            &ctx.accounts.token_program,
+            &ctx.accounts.order_escrow,
+            &ctx.accounts.pool_token_account,
+            &ctx.accounts.pool_authority,
+            &ctx.accounts.pool,
+            order.collateral,
+        )?;
+        close_pool_token_account(
+            &ctx.accounts.token_program,
+            &ctx.accounts.order_escrow,
+            &ctx.accounts.trader,
+            &ctx.accounts.pool_authority,
+            &ctx.accounts.pool,
+        )?;
+
+        let insurance_before = ctx.accounts.pool.insurance_fund;
+        let position = &mut ctx.accounts.position;
+        position.trader = order.trader;
+        position.market = order.market;
+        position.is_long = order.is_long;
+        position.bump = *ctx.bumps.get("position").unwrap();
+        let size = apply_open(
+            &mut ctx.accounts.pool,
+            &mut ctx.accounts.market,
+            position,
+            order.collateral,
+            order.leverage,
+            price,
+            now,
+        )?;
+        sync_insurance_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool_token_account,
+            &ctx.accounts.insurance_vault,
+            &ctx.accounts.pool_authority,
+            &ctx.accounts.pool,
+            insurance_before,
+        )?;
+
+        emit!(LimitOrderExecuted {
+            order: order.key(),
+            position: position.key(),
+            trader: order.trader,
+            keeper: ctx.accounts.keeper.key(),
+            market: position.market,
+            is_long: order.is_long,
+            limit_price: order.limit_price,
+            price,
+            size,
+            collateral: order.collateral,
+            keeper_fee: order.keeper_fee,
+        });
+
+        msg!("Limit Order Executed: Size ${} at {}", size, price);
+        Ok(())
+    }
+
+    /// Reduces a profitable position at the oracle price while pool NAV is below the ADL
+    /// threshold. Only whitelisted keepers may call it. Ranking happens off-chain; on-chain
+    /// the target must be in profit and must outrank every other position the keeper
+    /// passes in, from any market of the pool.
+    ///
+    /// remaining_accounts: (market, price_feed) pairs for every market (see `pool_nav`),
+    /// followed by any number of competing positions.
+    pub fn auto_deleverage(ctx: Context<AutoDeleverage>, size_delta: u64) -> Result<()> {
+        let nav_accounts = (ctx.accounts.pool.market_count * 2) as usize;
+        require!(
+            ctx.remaining_accounts.len() >= nav_accounts,
+            CustomError::InvalidMarketAccounts
+        );
+        let (nav_accounts, challengers) = ctx.remaining_accounts.split_at(nav_accounts);
+
+        // 1. ADL is only open while NAV is below the threshold share of liquidity
+        let marks = mark_markets(&ctx.accounts.pool, nav_accounts)?;
+        let nav = nav_from_marks(ctx.accounts.pool.total_liquidity, &marks)?;
+        let threshold = bps_of(ctx.accounts.pool.total_liquidity, ctx.accounts.pool.adl_threshold_bps)?;
+        require!(nav < threshold, CustomError::AdlNotActive);
+
+        // 2. Target must be profitable and rank at least as high as every challenger
+        let price = load_oracle_price(&ctx.accounts.price_feed, &ctx.accounts.market)?;
+        let position = &ctx.accounts.position;
+        let pnl = position_pnl(position.is_long, position.size, position.entry_price, price)?;
+        require!(pnl > 0, CustomError::NotDeleveragable);
+        let score = adl_score(pnl, position.size, position.collateral);
+        for info in challengers {
+            let other = Account::<PositionState>::try_from(info)?;
+            if other.key() == position.key() || other.size == 0 {
+                continue;
+            }
+            // Challengers are marked at their own market's price; every market of the
+            // pool with open interest was priced above
+            let other_price = marks
+                .iter()
+                .find(|mark| mark.market == other.market)
+                .map(|mark| mark.price)
+                .ok_or(CustomError::InvalidMarketAccounts)?;
+            let other_pnl = position_pnl(other.is_long, other.size, other.entry_price, other_price)?;
+            require!(
+                adl_score(other_pnl, other.size, other.collateral) <= score,
+                CustomError::NotDeleveragable
+            );
+        }
+
+        // 3. Reduce at the oracle price, without a close fee
+        let now = Clock::get()?.unix_timestamp;
+        let pool = &mut ctx.accounts.pool;
+        let insurance_before = pool.insurance_fund;
+        let market = &mut ctx.accounts.market;
+        let position = &mut ctx.accounts.position;
+        market.update_funding(position.market, now)?;
+        let size_delta = size_delta.min(position.size);
+        require!(size_delta > 0, CustomError::InvalidAmount);
+        let settlement = reduce_position(pool, market, position, size_delta, price, now, false)?;
+        sync_insurance_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool_token_account,
+            &ctx.accounts.insurance_vault,
+            &ctx.accounts.pool_authority,
+            pool,
+            insurance_before,
+        )?;
+
+        if settlement.payout > 0 {
+            transfer_from_pool(
+                &ctx.accounts.token_program,
+                &ctx.accounts.pool_token_account,
+                &ctx.accounts.trader_token_account,
+                &ctx.accounts.pool_authority,
+                pool,
+                settlement.payout,
+            )?;
+        }
+
+        emit!(PositionDeleveraged {
+            position: position.key(),
+            trader: position.trader,
+            keeper: ctx.accounts.keeper.key(),
+            market: position.market,
+            is_long: position.is_long,
+            size_delta,
+            remaining_size: position.size,
+            price,
+            pnl: settlement.pnl,
+            payout: settlement.payout,
+            score,
+            pool_nav: nav,
+        });
+
+        if position.size == 0 {
+            position.close(ctx.accounts.trader.to_account_info())?;
+        }
+
+        msg!("Position Deleveraged: -{} at {} (NAV {})", size_delta, price, nav);
+        Ok(())
+    }
+
+    pub fn update_funding(ctx: Context<UpdateFunding>) -> Result<()> {
+        let market_key = ctx.accounts.market.key();
+        let market = &mut ctx.accounts.market;
+        market.update_funding(market_key, Clock::get()?.unix_timestamp)?;
+        Ok(())
+    }
+
+    pub fn liquidate_position(ctx: Context<LiquidatePosition>) -> Result<()> {
+        let price = load_oracle_price(&ctx.accounts.price_feed, &ctx.accounts.market)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let market_key = ctx.accounts.market.key();
+        let market = &mut ctx.accounts.market;
+        market.update_funding(market_key, now)?;
+
+        let position = &ctx.accounts.position;
+        let pnl = position_pnl(position.is_long, position.size, position.entry_price, price)?;
+        let funding = position_funding(
+            position.is_long,
+            position.size,
+            position.entry_funding_index,
+            market.cumulative_funding_index,
+        )?;
+        let pnl = pnl.checked_sub(funding).ok_or(CustomError::MathOverflow)?;
+        let borrow_fees = borrow_fee(
+            position.size,
+            market.borrow_fee_bps_per_hour,
+            position.last_borrow_fee_time,
+            now,
+        )?;
+        market.remove_open_interest(position.is_long, position.size, position.entry_price)?;
+
+        // 1. Margin Check: equity (after funding and borrow fees) must be below maintenance
+        let equity = (position.collateral as i128) + (pnl as i128) - (borrow_fees as i128);
+        let maintenance_margin = market.maintenance_margin(position.size)?;
+        require!(
+            equity < maintenance_margin as i128,
+            CustomError::PositionNotLiquidatable
+        );
+
+        // 2. Settle: whatever equity is left pays the bounty, the rest goes to LPs
+        let pool = &mut ctx.accounts.pool;
+        let insurance_before = pool.insurance_fund;
+        let remaining = settle_pnl(pool, position, position.collateral, pnl)?;
+        let borrow_fees = borrow_fees.min(remaining);
+        distribute_fee(pool, borrow_fees)?;
+        let (bounty, to_pool, to_insurance) = liquidation_split(
+            remaining - borrow_fees,
+            bps_of(position.size, market.liquidation_fee_bps)?,
+            pool.insurance_liquidation_bps,
+        )?;
+        pool.insurance_fund = pool
+            .insurance_fund
+            .checked_add(to_insurance)
+            .ok_or(CustomError::MathOverflow)?;
+        pool.total_liquidity = pool
+            .total_liquidity
+            .checked_add(to_pool)
+            .ok_or(CustomError::MathOverflow)?;
+        sync_insurance_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.pool_token_account,
+            &ctx.accounts.insurance_vault,
+            &ctx.accounts.pool_authority,
+            pool,
+            insurance_before,
+        )?;
+
+        if bounty > 0 {
+            transfer_from_pool(
+                &ctx.accounts.token_program,
+                &ctx.accounts.pool_token_account,
+                &ctx.accounts.liquidator_token_account,
+                &ctx.accounts.pool_authority,
+                pool,
+                bounty,
+            )?;
+        }
+
+        emit!(PositionLiquidated {
+            position: position.key(),
+            trader: position.trader,
+            liquidator: ctx.accounts.liquidator.key(),
+            market: position.market,
+            is_long: position.is_long,
+            size: position.size,
+            collateral: position.collateral,
+            price,
+            pnl,
+            bounty,
+            to_pool,
+            to_insurance,
+        });
+
+        msg!(
+            "Position Liquidated: Bounty {} To Pool {} To Insurance {}",
+            bounty,
+            to_pool,
+            to_insurance
+        );
+        Ok(())
+    }
+}
+
+// --- Helpers ---
+
+/// Reads the market's oracle price, rejecting unbound oracle accounts
+fn load_oracle_price(price_feed: &AccountInfo, market: &Market) -> Result<i64> {
+    require_keys_eq!(price_feed.key(), market.oracle, CustomError::InvalidOracle);
+
+    match market.oracle_kind {
+        OracleKind::Pyth => load_pyth_price(price_feed, market),
+        OracleKind::FairLaunchTwap => load_twap_price(price_feed, market),
+    }
+}
+
+/// Pyth price (in the feed's native exponent), rejecting stale prices and prices
+/// with too wide a confidence interval
+fn load_pyth_price(price_feed: &AccountInfo, market: &Market) -> Result<i64> {
+    let price_feed = load_price_feed_from_account_info(price_feed)
+        .map_err(|_| error!(CustomError::InvalidOracle))?;
+    let current_price = price_feed
+        .get_price_no_older_than(Clock::get()?.unix_timestamp, market.max_price_age_secs)
+        .ok_or(CustomError::StaleOraclePrice)?;
+    require!(current_price.price > 0, CustomError::InvalidOracle);
+
+    let max_conf = bps_of(current_price.price as u64, market.max_confidence_bps)?;
+    require!(
+        current_price.conf <= max_conf,
+        CustomError::OracleConfidenceTooWide
+    );
+    Ok(current_price.price)
+}
+
+/// Fair-launch curve TWAP (SOL per token, scaled by `fair_launch::PRICE_PRECISION`).
+/// The curve must have traded within `max_price_age_secs`, and spot may not stray more
+/// than `max_confidence_bps` from the TWAP, which rejects reads while the curve is
+/// being pushed around.
+fn load_twap_price(observations: &AccountInfo, market: &Market) -> Result<i64> {
+    let quote = read_twap(observations, market)?;
+    require!(
+        quote.age_secs <= market.max_price_age_secs,
+        CustomError::StaleOraclePrice
+    );
+    require!(
+        quote.divergence_bps <= market.max_confidence_bps,
+        CustomError::OracleConfidenceTooWide
+    );
+    Ok(quote.price)
+}
+
+/// A curve TWAP with what is needed to judge it
+pub struct TwapQuote {
+    pub price: i64,
+    pub age_secs: u64,       // Since the curve's last trade
+    pub divergence_bps: u64, // Between the TWAP and the curve's spot price
+}
+
+impl TwapQuote {
+    fn is_trusted(&self, market: &Market) -> bool {
+        self.age_secs <= market.max_price_age_secs && self.divergence_bps <= market.max_confidence_bps
+    }
+}
+
+/// Curve TWAP over the market's window, without freshness or divergence checks
+fn read_twap(observations: &AccountInfo, market: &Market) -> Result<TwapQuote> {
+    let observations = Account::<fair_launch::Observations>::try_from(observations)?;
+    let now = Clock::get()?.unix_timestamp as u64;
+    let twap = observations.twap(now, market.twap_window_secs);
+    require!(twap > 0, CustomError::InvalidOracle);
+
+    Ok(TwapQuote {
+        price: i64::try_from(twap).map_err(|_| error!(CustomError::MathOverflow))?,
+        age_secs: now.saturating_sub(observations.last_update()),
+        divergence_bps: fair_launch::price_move_bps(twap, observations.last_price)?,
+    })
+}
+
+/// Trader PnL in collateral units: size * (exit - entry) / entry, negated for shorts
+pub fn position_pnl(is_long: bool, size: u64, entry_price: i64, exit_price: i64) -> Result<i64> {
+    require!(entry_price > 0, CustomError::InvalidOracle);
+    let delta = (exit_price as i128) - (entry_price as i128);
+    let pnl = (size as i128)
+        .checked_mul(delta)
+        .ok_or(CustomError::MathOverflow)?
+        / entry_price as i128;
+    let pnl = if is_long { pnl } else { -pnl };
+    i64::try_from(pnl).map_err(|_| error!(CustomError::MathOverflow))
+}
+
+/// Value of the pool owned by LP share holders:
+/// deposits + realized PnL (`total_liquidity`) - traders' unrealized PnL.
+/// `markets` must list every market of the pool as (market, price_feed) pairs.
+fn pool_nav(pool: &Account<PoolState>, markets: &[AccountInfo]) -> Result<u64> {
+    let marks = mark_markets(pool, markets)?;
+    nav_from_marks(pool.total_liquidity, &marks)
+}
+
+/// A pool market with open interest, priced at its oracle
+pub struct MarketMark {
+    pub market: Pubkey,
+    pub price: i64,
+    pub trader_pnl: i64,
+}
+
+/// Prices every market of the pool that has open interest. `markets` must hold one
+/// (market, price_feed) pair for each of the pool's `market_count` markets.
+fn mark_markets(pool: &Account<PoolState>, markets: &[AccountInfo]) -> Result<Vec<MarketMark>> {
+    require!(
+        markets.len() as u64 == pool.market_count * 2,
+        CustomError::InvalidMarketAccounts
+    );
+
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(markets.len() / 2);
+    let mut marks = Vec::with_capacity(markets.len() / 2);
+    for pair in markets.chunks(2) {
+        let market = Account::<Market>::try_from(&pair[0])?;
+        require_keys_eq!(market.pool, pool.key(), CustomError::InvalidMarketAccounts);
+        require!(!seen.contains(&market.key()), CustomError::InvalidMarketAccounts);
+        seen.push(market.key());
+
+        if market.long_oi == 0 && market.short_oi == 0 {
+            continue;
+        }
+        let (price, trader_pnl) = match market.oracle_kind {
+            OracleKind::Pyth => {
+                let price = load_oracle_price(&pair[1], &market)?;
+                (price, market.unrealized_trader_pnl(price)?)
+            }
+            // A listing's curve may stop trading. Rather than block every NAV read,
+            // mark it at its last TWAP and, unless that price is trusted, count only
+            // what it could owe traders.
+            OracleKind::FairLaunchTwap => {
+                require_keys_eq!(pair[1].key(), market.oracle, CustomError::InvalidOracle);
+                let quote = read_twap(&pair[1], &market)?;
+                let trader_pnl = if quote.is_trusted(&market) {
+                    market.unrealized_trader_pnl(quote.price)?
+                } else {
+                    market.unrealized_trader_profit(quote.price)?
+                };
+                (quote.price, trader_pnl)
+            }
+        };
+        marks.push(MarketMark {
+            market: market.key(),
+            price,
+            trader_pnl,
+        });
+    }
+    Ok(marks)
+}
+
+/// Pool NAV: liquidity minus aggregate unrealized trader PnL, floored at zero
+fn nav_from_marks(total_liquidity: u64, marks: &[MarketMark]) -> Result<u64> {
+    let trader_pnl: i128 = marks.iter().map(|mark| mark.trader_pnl as i128).sum();
+    let nav = (total_liquidity as i128 - trader_pnl).max(0);
+    u64::try_from(nav).map_err(|_| error!(CustomError::MathOverflow))
+}
+
+/// Size-weighted harmonic average entry after adding (or removing) `size` at `price`.
+/// Harmonic keeps aggregate PnL exact: sum(size_i / entry_i) == total / avg.
+fn average_entry_price(total: u64, avg: i64, size: u64, price: i64, add: bool) -> Result<i64> {
+    if total == 0 || avg <= 0 {
+        return Ok(if add { price } else { 0 });
+    }
+    let (total, avg, size, price) = (total as i128, avg as i128, size as i128, price as i128);
+    let new_total = if add { total + size } else { total - size };
+    if new_total <= 0 {
+        return Ok(0);
+    }
+
+    // new_total / (total / avg +- size / price)
+    let weighted = size.checked_mul(avg).ok_or(CustomError::MathOverflow)?;
+    let base = total.checked_mul(price).ok_or(CustomError::MathOverflow)?;
+    let denominator = if add { base + weighted } else { base - weighted };
+    if denominator <= 0 {
+        // Rounding dust after removals: keep the previous average
+        return Ok(avg as i64);
+    }
+    let numerator = new_total
+        .checked_mul(avg)
+        .and_then(|n| n.checked_mul(price))
+        .ok_or(CustomError::MathOverflow)?;
+    i64::try_from(numerator / denominator).map_err(|_| error!(CustomError::MathOverflow))
+}
+
+/// Shares minted for a deposit of `amount` into a pool worth `nav`
+fn shares_for_deposit(amount: u64, supply: u64, nav: u64) -> Result<u64> {
+    if supply == 0 {
+        return Ok(amount);
+    }
+    // Outstanding shares over a worthless pool cannot be priced
+    require!(nav > 0, CustomError::PoolInsolvent);
+    let shares = (amount as u128)
+        .checked_mul(supply as u128)
+        .ok_or(CustomError::MathOverflow)?
+        / nav as u128;
+    u64::try_from(shares).map_err(|_| error!(CustomError::MathOverflow))
+}
+
+/// Pool tokens returned for burning `shares` out of `supply`
+fn amount_for_shares(shares: u64, supply: u64, nav: u64) -> Result<u64> {
+    require!(supply > 0 && shares <= supply, CustomError::InvalidAmount);
+    let amount = (shares as u128)
+        .checked_mul(nav as u128)
+        .ok_or(CustomError::MathOverflow)?
+        / supply as u128;
+    u64::try_from(amount).map_err(|_| error!(CustomError::MathOverflow))
+}
+
+/// Shares (and collateral) a request of `requested` shares can redeem now: all of it, or
+/// the pro-rata part that fits in the epoch's remaining `available` amount
+fn withdrawal_fill(requested: u64, supply: u64, nav: u64, available: u64) -> Result<(u64, u64)> {
+    let mut shares = requested;
+    let mut amount = amount_for_shares(shares, supply, nav)?;
+    if amount > available {
+        shares = pro_rata(requested, available, amount)?;
+        amount = amount_for_shares(shares, supply, nav)?;
+    }
+    require!(shares > 0, CustomError::EpochWithdrawalCapReached);
+    Ok((shares, amount))
+}
+
+/// `amount * bps / 10_000`
+fn bps_of(amount: u64, bps: u64) -> Result<u64> {
+    let result = (amount as u128)
+        .checked_mul(bps as u128)
+        .ok_or(CustomError::MathOverflow)?
+        / 10_000;
+    u64::try_from(result).map_err(|_| error!(CustomError::MathOverflow))
+}
+
+/// Funding owed by a position since it was opened (positive = trader pays)
+pub fn position_funding(
+    is_long: bool,
+    size: u64,
+    entry_funding_index: i128,
+    current_funding_index: i128,
+) -> Result<i64> {
+    let delta = current_funding_index
+        .checked_sub(entry_funding_index)
+        .ok_or(CustomError::MathOverflow)?;
+    let owed = (size as i128)
+        .checked_mul(delta)
+        .ok_or(CustomError::MathOverflow)?
+        / FUNDING_PRECISION;
+    let owed = if is_long { owed } else { -owed };
+    i64::try_from(owed).map_err(|_| error!(CustomError::MathOverflow))
+}
+
+/// Borrow fee accrued on `size` between `since` and `now`
+fn borrow_fee(size: u64, bps_per_hour: u64, since: i64, now: i64) -> Result<u64> {
+    let elapsed = now.saturating_sub(since).max(0) as u128;
+    let fee = (size as u128)
+        .checked_mul(bps_per_hour as u128)
+        .and_then(|f| f.checked_mul(elapsed))
+        .ok_or(CustomError::MathOverflow)?
+        / (3_600 * 10_000);
+    u64::try_from(fee).map_err(|_| error!(CustomError::MathOverflow))
+}
+
+/// Splits a collected fee between LPs and the protocol according to `admin_fee_bps`
+fn distribute_fee(pool: &mut PoolState, fee: u64) -> Result<()> {
+    let protocol_share = bps_of(fee, pool.admin_fee_bps)?;
+    let insurance_share = bps_of(fee - protocol_share, pool.insurance_fee_bps)?;
+    pool.protocol_fees = pool
+        .protocol_fees
+        .checked_add(protocol_share)
+        .ok_or(CustomError::MathOverflow)?;
+    pool.insurance_fund = pool
+        .insurance_fund
+        .checked_add(insurance_share)
+        .ok_or(CustomError::MathOverflow)?;
+    pool.total_liquidity = pool
+        .total_liquidity
+        .checked_add(fee - protocol_share - insurance_share)
+        .ok_or(CustomError::MathOverflow)?;
+    Ok(())
+}
+
+/// `amount * part / total`
+fn pro_rata(amount: u64, part: u64, total: u64) -> Result<u64> {
+    require!(total > 0, CustomError::MathOverflow);
+    let result = (amount as u128)
+        .checked_mul(part as u128)
+        .ok_or(CustomError::MathOverflow)?
+        / total as u128;
+    u64::try_from(result).map_err(|_| error!(CustomError::MathOverflow))
+}
+
+/// Settles accrued funding and borrow fees against the position's collateral and
+/// restarts its accrual checkpoints. `market` funding must already be up to date.
+/// Returns (funding owed, borrow fee charged).
+fn settle_accruals(
+    pool: &mut PoolState,
+    market: &Market,
+    position: &mut PositionState,
+    now: i64,
+) -> Result<(i64, u64)> {
+    let funding = position_funding(
+        position.is_long,
+        position.size,
+        position.entry_funding_index,
+        market.cumulative_funding_index,
+    )?;
+    if funding >= 0 {
+        let paid = (funding as u64).min(position.collateral);
+        position.collateral -= paid;
+        pool.total_liquidity = pool
+            .total_liquidity
+            .checked_add(paid)
+            .ok_or(CustomError::MathOverflow)?;
+    } else {
+        let received = funding.unsigned_abs().min(pool.total_liquidity);
+        pool.total_liquidity -= received;
+        position.collateral = position
+            .collateral
+            .checked_add(received)
+            .ok_or(CustomError::MathOverflow)?;
+    }
+
+    let fee = borrow_fee(
+        position.size,
+        market.borrow_fee_bps_per_hour,
+        position.last_borrow_fee_time,
+        now,
+    )?
+    .min(position.collateral);
+    position.collateral -= fee;
+    distribute_fee(pool, fee)?;
+
+    position.entry_funding_index = market.cumulative_funding_index;
+    position.last_borrow_fee_time = now;
+    Ok((funding, fee))
+}
+
+/// Opens (or adds to) a position at `price` with collateral already in the pool vault:
+/// enforces leverage and OI limits, charges the open fee and records the increase.
+/// Returns the notional size added.
+fn apply_open(
+    pool: &mut PoolState,
+    market: &mut Market,
+    position: &mut PositionState,
+    collateral: u64,
+    leverage: u64,
+    price: i64,
+    now: i64,
+) -> Result<u64> {
+    let size = market.validate_open(
+        pool.total_liquidity,
+        position.is_long,
+        position.size,
+        position.collateral,
+        collateral,
+        leverage,
+    )?;
+    market.update_funding(position.market, now)?;
+    let impact = market.price_impact(position.is_long, size, true)?;
+    market.add_open_interest(position.is_long, size, price)?;
+
+    // Open fee is split between LPs and protocol; price impact goes to LPs (or is rebated)
+    let open_fee = bps_of(size, market.open_fee_bps)?;
+    let impact_fee = impact.max(0) as u64;
+    require!(
+        open_fee.saturating_add(impact_fee) < collateral,
+        CustomError::InsufficientCollateral
+    );
+    distribute_fee(pool, open_fee)?;
+    let impact = settle_price_impact(pool, market, impact, impact_fee)?;
+
+    let collateral = collateral - open_fee - impact.max(0) as u64 + impact.min(0).unsigned_abs();
+    increase_position(pool, market, position, size, collateral, price, now)?;
+    Ok(size)
+}
+
+/// Adds `size` and `collateral` at `price` to a fresh or existing position. Accruals on
+/// an existing position are settled first and the entry price becomes the size-weighted
+/// (harmonic) average, matching the market aggregate.
+fn increase_position(
+    pool: &mut PoolState,
+    market: &Market,
+    position: &mut PositionState,
+    size: u64,
+    collateral: u64,
+    price: i64,
+    now: i64,
+) -> Result<()> {
+    if position.size > 0 {
+        settle_accruals(pool, market, position, now)?;
+    } else {
+        position.timestamp = now;
+        position.entry_funding_index = market.cumulative_funding_index;
+        position.last_borrow_fee_time = now;
+    }
+
+    position.entry_price = average_entry_price(position.size, position.entry_price, size, price, true)?;
+    position.size = position
+        .size
+        .checked_add(size)
+        .ok_or(CustomError::MathOverflow)?;
+    position.collateral = position
+        .collateral
+        .checked_add(collateral)
+        .ok_or(CustomError::MathOverflow)?;
+    Ok(())
+}
+
+/// Outcome of realizing part (or all) of a position
+pub struct Settlement {
+    pub pnl: i64,
+    pub funding: i64,
+    pub fees: u64,
+    pub payout: u64,
+}
+
+/// Closes `size_delta` of a position at `price`: settles accruals, realizes PnL against
+/// a pro-rata slice of collateral and (unless waived) charges the close fee out of what
+/// is owed. `market` funding must already be up to date.
+fn reduce_position(
+    pool: &mut PoolState,
+    market: &mut Market,
+    position: &mut PositionState,
+    size_delta: u64,
+    price: i64,
+    now: i64,
+    charge_close_fee: bool,
+) -> Result<Settlement> {
+    require!(size_delta <= position.size, CustomError::InvalidAmount);
+    let (funding, borrow_fees) = settle_accruals(pool, market, position, now)?;
+
+    let pnl = position_pnl(position.is_long, size_delta, position.entry_price, price)?;
+    let collateral_delta = pro_rata(position.collateral, size_delta, position.size)?;
+    let impact = market.price_impact(position.is_long, size_delta, false)?;
+    market.remove_open_interest(position.is_long, size_delta, position.entry_price)?;
+    position.size -= size_delta;
+    position.collateral -= collateral_delta;
+
+    let owed = settle_pnl(pool, position, collateral_delta, pnl)?;
+    let close_fee = if charge_close_fee {
+        bps_of(size_delta, market.close_fee_bps)?.min(owed)
+    } else {
+        0
+    };
+    distribute_fee(pool, close_fee)?;
+
+    // Fee-free reductions (ADL) still earn rebates but pay no impact fee
+    let impact = if charge_close_fee { impact } else { impact.min(0) };
+    let impact = settle_price_impact(pool, market, impact, owed - close_fee)?;
+    let impact_fee = impact.max(0) as u64;
+    let rebate = impact.min(0).unsigned_abs();
+
+    Ok(Settlement {
+        pnl,
+        funding,
+        fees: borrow_fees + close_fee + impact_fee,
+        payout: owed - close_fee - impact_fee + rebate,
+    })
+}
+
+/// Books a signed price impact against LP liquidity: fees (capped at `max_fee`) go to
+/// LPs and fund the market's rebate budget, rebates are paid out of that budget.
+/// Returns the impact actually applied.
+fn settle_price_impact(pool: &mut PoolState, market: &mut Market, impact: i64, max_fee: u64) -> Result<i64> {
+    if impact >= 0 {
+        let fee = (impact as u64).min(max_fee);
+        market.impact_fee_balance = market
+            .impact_fee_balance
+            .checked_add(fee)
+            .ok_or(CustomError::MathOverflow)?;
+        pool.total_liquidity = pool
+            .total_liquidity
+            .checked_add(fee)
+            .ok_or(CustomError::MathOverflow)?;
+        Ok(fee as i64)
+    } else {
+        let rebate = impact
+            .unsigned_abs()
+            .min(market.impact_fee_balance)
+            .min(pool.total_liquidity);
+        market.impact_fee_balance -= rebate;
+        pool.total_liquidity -= rebate;
+        Ok(-(rebate as i64))
+    }
+}
+
+/// ADL ranking: PnL as a fraction of collateral times effective leverage,
+/// i.e. pnl * size / collateral^2 (in bps). Losing positions score zero.
+fn adl_score(pnl: i64, size: u64, collateral: u64) -> u128 {
+    if pnl <= 0 {
+        return 0;
+    }
+    if collateral == 0 {
+        return u128::MAX;
+    }
+    (pnl as u128)
+        .saturating_mul(size as u128)
+        .saturating_mul(10_000)
+        / (collateral as u128 * collateral as u128)
+}
+
+/// Whether `price` has crossed a trigger. Take-profits fire in the position's favour,
+/// stop-losses against it.
+fn trigger_crossed(kind: TriggerKind, is_long: bool, trigger_price: i64, price: i64) -> bool {
+    match (kind, is_long) {
+        (TriggerKind::TakeProfit, true) | (TriggerKind::StopLoss, false) => price >= trigger_price,
+        (TriggerKind::TakeProfit, false) | (TriggerKind::StopLoss, true) => price <= trigger_price,
+    }
+}
+
+/// Splits what is left of a liquidated position's collateral: the liquidator's `bounty`
+/// comes first, the rest is shared between the insurance fund and LPs.
+/// Returns (bounty, to pool, to insurance).
+fn liquidation_split(remaining: u64, bounty: u64, insurance_liquidation_bps: u64) -> Result<(u64, u64, u64)> {
+    let bounty = bounty.min(remaining);
+    let penalty = remaining - bounty;
+    let to_insurance = bps_of(penalty, insurance_liquidation_bps)?;
+    Ok((bounty, penalty - to_insurance, to_insurance))
+}
+
+/// Applies realized trader PnL to pool liquidity and returns the amount owed to the trader
+fn settle_pnl(
+    pool: &mut PoolState,
+    position: &PositionState,
+    collateral: u64,
+    pnl: i64,
+) -> Result<u64> {
+    if pnl >= 0 {
+        // Profit can never exceed what LPs have deposited
+        let profit = (pnl as u64).min(pool.total_liquidity);
+        pool.total_liquidity -= profit;
+        collateral.checked_add(profit).ok_or(error!(CustomError::MathOverflow))
+    } else {
+        // Losses beyond the posted collateral are not recoverable from the trader
+        let loss = pnl.unsigned_abs().min(collateral);
+        pool.total_liquidity = pool
+            .total_liquidity
+            .checked_add(loss)
+            .ok_or(CustomError::MathOverflow)?;
+        cover_bad_debt(pool, position, pnl.unsigned_abs() - loss)?;
+        Ok(collateral - loss)
+    }
+}
+
+/// Makes LPs whole for a trader loss the collateral could not absorb, drawing on the
+/// insurance fund first. Whatever the fund cannot cover stays with the LPs.
+fn cover_bad_debt(pool: &mut PoolState, position: &PositionState, shortfall: u64) -> Result<()> {
+    if shortfall == 0 {
+        return Ok(());
+    }
+    let covered = shortfall.min(pool.insurance_fund);
+    let uncovered = shortfall - covered;
+    pool.insurance_fund -= covered;
+    pool.total_liquidity = pool
+        .total_liquidity
+        .checked_add(covered)
+        .ok_or(CustomError::MathOverflow)?;
+    pool.insurance_drawn = pool
+        .insurance_drawn
+        .checked_add(covered)
+        .ok_or(CustomError::MathOverflow)?;
+    pool.uncovered_bad_debt = pool
+        .uncovered_bad_debt
+        .checked_add(uncovered)
+        .ok_or(CustomError::MathOverflow)?;
+
+    emit!(InsuranceFundDrawn {
+        trader: position.trader,
+        market: position.market,
+        is_long: position.is_long,
+        shortfall,
+        covered,
+        uncovered,
+        balance: pool.insurance_fund,
+    });
+
+    msg!("Bad Debt: {} covered by insurance, {} uncovered", covered, uncovered);
+    Ok(())
+}
+
+/// Transfers tokens out of the pool vault, signed by the pool authority PDA
+fn transfer_from_pool<'info>(
+    token_program: &Program<'info, Token>,
+    pool_token_account: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    pool_authority: &AccountInfo<'info>,
+    pool: &Account<'info, PoolState>,
+    amount: u64,
+) -> Result<()> {
+    let pool_key = pool.key();
+    let seeds = &[b"pool_authority", pool_key.as_ref(), &[pool.authority_bump]];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = Transfer {
+        from: pool_token_account.to_account_info(),
+        to: to.to_account_info(),
+        authority: pool_authority.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer);
+    token::transfer(cpi_ctx, amount)
+}
+
+/// Moves the insurance fund's ledger change since `insurance_before` between the pool
+/// vault and the insurance vault, so fee and liquidation inflows and bad-debt draws are
+/// backed by real balances
+fn sync_insurance_vault<'info>(
+    token_program: &Program<'info, Token>,
+    pool_token_account: &Account<'info, TokenAccount>,
+    insurance_vault: &Account<'info, TokenAccount>,
+    pool_authority: &AccountInfo<'info>,
+    pool: &Account<'info, PoolState>,
+    insurance_before: u64,
+) -> Result<()> {
+    if pool.insurance_fund > insurance_before {
+        let amount = pool.insurance_fund - insurance_before;
+        transfer_from_pool(token_program, pool_token_account, insurance_vault, pool_authority, pool, amount)
+    } else if pool.insurance_fund < insurance_before {
+        let amount = insurance_before - pool.insurance_fund;
+        transfer_from_pool(token_program, insurance_vault, pool_token_account, pool_authority, pool, amount)
+    } else {
+        Ok(())
+    }
+}
+
+/// Closes a token account owned by the pool authority (e.g. an emptied order escrow)
+fn close_pool_token_account<'info>(
+    token_program: &Program<'info, Token>,
+    account: &Account<'info, TokenAccount>,
+    destination: &AccountInfo<'info>,
+    pool_authority: &AccountInfo<'info>,
+    pool: &Account<'info, PoolState>,
+) -> Result<()> {
+    let pool_key = pool.key();
+    let seeds = &[b"pool_authority", pool_key.as_ref(), &[pool.authority_bump]];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = CloseAccount {
+        account: account.to_account_info(),
+        destination: destination.clone(),
+        authority: pool_authority.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer);
+    token::close_account(cpi_ctx)
+}
+
+// --- Account Structs ---
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(init, payer = admin, space = 8 + 32 + 8 + 8 + 32 + 32 + 1 + 32 + 8 + 8 + 8 + 8 + 32 + 8 + 8 + 8 + 8 + 8 + 32 * MAX_ADL_KEEPERS + 8 + 8 + 8 + 8 + 8)]
+    pub pool: Account<'info, PoolState>,
+    pub collateral_mint: Account<'info, Mint>,
+    /// CHECK: PDA signer for the pool vault
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump)]
+    pub pool_authority: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = admin,
+        token::mint = collateral_mint,
+        token::authority = pool_authority,
+        seeds = [b"pool_vault", pool.key().as_ref()],
+        bump
+    )]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = admin,
+        token::mint = collateral_mint,
+        token::authority = pool_authority,
+        seeds = [b"insurance_vault", pool.key().as_ref()],
+        bump
+    )]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = admin,
+        mint::decimals = collateral_mint.decimals,
+        mint::authority = pool_authority,
+        seeds = [b"lp_mint", pool.key().as_ref()],
+        bump
+    )]
+    pub lp_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct AddLiquidity<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, PoolState>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.token_account)]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.lp_mint)]
+    pub lp_mint: Account<'info, Mint>,
+    #[account(mut, token::mint = lp_mint)]
+    pub user_lp_account: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault and LP mint
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    pub user: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RequestWithdrawal<'info> {
+    pub pool: Account<'info, PoolState>,
+    #[account(address = pool.lp_mint)]
+    pub lp_mint: Account<'info, Mint>,
+    #[account(mut, token::mint = lp_mint)]
+    pub user_lp_account: Account<'info, TokenAccount>,
+    // One open request per LP per pool
+    #[account(
+        init,
+        payer = user,
+        space = WithdrawalRequest::LEN,
+        seeds = [b"withdrawal", pool.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+    #[account(
+        init,
+        payer = user,
+        token::mint = lp_mint,
+        token::authority = pool_authority,
+        seeds = [b"withdrawal_escrow", withdrawal_request.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_escrow: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault and escrows
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteWithdrawal<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, PoolState>,
+    #[account(mut, has_one = pool, has_one = owner)]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+    #[account(mut, seeds = [b"withdrawal_escrow", withdrawal_request.key().as_ref()], bump)]
+    pub withdrawal_escrow: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.lp_mint)]
+    pub lp_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        token::mint = pool.collateral_mint,
+        token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    /// CHECK: Receives the request and escrow rent once fully processed
+    #[account(mut)]
+    pub owner: AccountInfo<'info>,
+    #[account(mut, address = pool.token_account)]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault and escrows
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelWithdrawal<'info> {
+    pub pool: Account<'info, PoolState>,
+    #[account(
+        mut,
+        close = user,
+        has_one = pool,
+        constraint = withdrawal_request.owner == user.key() @ CustomError::Unauthorized
+    )]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+    #[account(mut, seeds = [b"withdrawal_escrow", withdrawal_request.key().as_ref()], bump)]
+    pub withdrawal_escrow: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = pool.lp_mint)]
+    pub user_lp_account: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault and escrows
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(is_long: bool)]
+pub struct OpenPosition<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, PoolState>,
+    #[account(
+        mut,
+        has_one = pool,
+        constraint = market.status == MarketStatus::Active @ CustomError::MarketNotActive
+    )]
+    pub market: Account<'info, Market>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = PositionState::LEN,
+        seeds = [b"position", user.key().as_ref(), market.key().as_ref(), &[is_long as u8]],
+        bump
+    )]
+    pub position: Account<'info, PositionState>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.token_account)]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.insurance_vault)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    /// CHECK: Pyth Price Feed Account, bound to the market
+    #[account(address = market.oracle @ CustomError::InvalidOracle)]
+    pub price_feed: AccountInfo<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_id: u64)]
+pub struct PlaceLimitOrder<'info> {
+    pub pool: Account<'info, PoolState>,
+    #[account(
+        has_one = pool,
+        constraint = market.status == MarketStatus::Active @ CustomError::MarketNotActive
+    )]
+    pub market: Account<'info, Market>,
+    #[account(
+        init,
+        payer = user,
+        space = LimitOrder::LEN,
+        seeds = [b"limit_order", user.key().as_ref(), market.key().as_ref(), &order_id.to_le_bytes()],
+        bump
+    )]
+    pub limit_order: Account<'info, LimitOrder>,
+    #[account(
+        init,
+        payer = user,
+        token::mint = collateral_mint,
+        token::authority = pool_authority,
+        seeds = [b"order_escrow", limit_order.key().as_ref()],
+        bump
+    )]
+    pub order_escrow: Account<'info, TokenAccount>,
+    #[account(address = pool.collateral_mint)]
+    pub collateral_mint: Account<'info, Mint>,
+    /// CHECK: PDA signer for the pool vault and order escrows
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct CancelLimitOrder<'info> {
+    pub pool: Account<'info, PoolState>,
+    #[account(
+        mut,
+        close = user,
+        constraint = limit_order.trader == user.key() @ CustomError::Unauthorized
+    )]
+    pub limit_order: Account<'info, LimitOrder>,
+    #[account(mut, seeds = [b"order_escrow", limit_order.key().as_ref()], bump)]
+    pub order_escrow: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault and order escrows
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    #[account(mut, token::mint = pool.collateral_mint)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteLimitOrder<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, PoolState>,
+    #[account(
+        mut,
+        has_one = pool,
+        constraint = market.status == MarketStatus::Active @ CustomError::MarketNotActive
+    )]
+    pub market: Account<'info, Market>,
+    // Keeper collects the escrowed fee plus the order rent
+    #[account(mut, close = keeper, has_one = market)]
+    pub limit_order: Account<'info, LimitOrder>,
+    #[account(mut, seeds = [b"order_escrow", limit_order.key().as_ref()], bump)]
+    pub order_escrow: Account<'info, TokenAccount>,
+    // Keeper fronts the position rent; the keeper fee is expected to cover it
+    #[account(
+        init_if_needed,
+        payer = keeper,
+        space = PositionState::LEN,
+        seeds = [
+            b"position",
+            limit_order.trader.as_ref(),
+            market.key().as_ref(),
+            &[limit_order.is_long as u8]
+        ],
+        bump
+    )]
+    pub position: Account<'info, PositionState>,
+    /// CHECK: Receives the escrow rent back
+    #[account(mut, address = limit_order.trader)]
+    pub trader: AccountInfo<'info>,
+    #[account(mut, address = pool.token_account)]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.insurance_vault)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault and order escrows
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    /// CHECK: Pyth Price Feed Account, bound to the market
+    #[account(address = market.oracle @ CustomError::InvalidOracle)]
+    pub price_feed: AccountInfo<'info>,
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ModifyPosition<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, PoolState>,
+    #[account(
+        mut,
+        has_one = pool,
+        constraint = market.status != MarketStatus::Paused @ CustomError::MarketPaused
+    )]
+    pub market: Account<'info, Market>,
+    #[account(
+        mut,
+        has_one = market,
+        constraint = position.trader == user.key() @ CustomError::Unauthorized
+    )]
+    pub position: Account<'info, PositionState>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.token_account)]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.insurance_vault)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    /// CHECK: Pyth Price Feed Account, bound to the market
+    #[account(address = market.oracle @ CustomError::InvalidOracle)]
+    pub price_feed: AccountInfo<'info>,
+    pub user: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(kind: TriggerKind)]
+pub struct PlaceTriggerOrder<'info> {
+    #[account(constraint = position.trader == user.key() @ CustomError::Unauthorized)]
+    pub position: Account<'info, PositionState>,
+    #[account(
+        init,
+        payer = user,
+        space = TriggerOrder::LEN,
+        seeds = [b"trigger_order", position.key().as_ref(), &[kind as u8]],
+        bump
+    )]
+    pub trigger_order: Account<'info, TriggerOrder>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelTriggerOrder<'info> {
+    #[account(
+        mut,
+        close = user,
+        constraint = trigger_order.trader == user.key() @ CustomError::Unauthorized
+    )]
+    pub trigger_order: Account<'info, TriggerOrder>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteTriggerOrder<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, PoolState>,
+    #[account(
+        mut,
+        has_one = pool,
+        constraint = market.status != MarketStatus::Paused @ CustomError::MarketPaused
+    )]
+    pub market: Account<'info, Market>,
+    #[account(mut, has_one = market)]
+    pub position: Account<'info, PositionState>,
+    // Keeper collects the escrowed fee plus the order rent
+    #[account(mut, close = keeper, has_one = position)]
+    pub trigger_order: Account<'info, TriggerOrder>,
+    /// CHECK: Receives the position rent on a full close
+    #[account(mut, address = position.trader)]
+    pub trader: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::mint = pool.collateral_mint,
+        token::authority = position.trader
+    )]
+    pub trader_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.token_account)]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.insurance_vault)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    /// CHECK: Pyth Price Feed Account, bound to the market
+    #[account(address = market.oracle @ CustomError::InvalidOracle)]
+    pub price_feed: AccountInfo<'info>,
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePosition<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, PoolState>,
+    #[account(
+        mut,
+        has_one = pool,
+        constraint = market.status != MarketStatus::Paused @ CustomError::MarketPaused
+    )]
+    pub market: Account<'info, Market>,
+    #[account(
+        mut,
+        close = user,
+        has_one = market,
+        constraint = position.trader == user.key() @ CustomError::Unauthorized
+    )]
+    pub position: Account<'info, PositionState>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.token_account)]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.insurance_vault)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    /// CHECK: Pyth Price Feed Account, bound to the market
+    #[account(address = market.oracle @ CustomError::InvalidOracle)]
+    pub price_feed: AccountInfo<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct LiquidatePosition<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, PoolState>,
+    #[account(mut, has_one = pool)]
+    pub market: Account<'info, Market>,
+    #[account(mut, close = trader, has_one = market)]
+    pub position: Account<'info, PositionState>,
+    /// CHECK: Receives the position account rent
+    #[account(mut, address = position.trader)]
+    pub trader: AccountInfo<'info>,
+    #[account(mut, address = pool.token_account)]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.insurance_vault)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    /// CHECK: Pyth Price Feed Account, bound to the market
+    #[account(address = market.oracle @ CustomError::InvalidOracle)]
+    pub price_feed: AccountInfo<'info>,
+    #[account(mut)]
+    pub liquidator_token_account: Account<'info, TokenAccount>,
+    pub liquidator: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(symbol: String)]
+pub struct CreateMarket<'info> {
+    #[account(mut, has_one = admin @ CustomError::Unauthorized)]
+    pub pool: Account<'info, PoolState>,
+    #[account(
+        init,
+        payer = admin,
+        space = Market::LEN,
+        seeds = [b"market", pool.key().as_ref(), symbol.as_bytes()],
+        bump
+    )]
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ListGraduatedToken<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, PoolState>,
+    // Owner and discriminator checks guarantee a genuine fair_launch curve
+    pub curve_state: Account<'info, fair_launch::CurveState>,
+    #[account(
+        seeds = [b"observations", curve_state.token_mint.as_ref()],
+        bump,
+        seeds::program = fair_launch::ID
+    )]
+    pub observations: Account<'info, fair_launch::Observations>,
+    // One listing per token
+    #[account(
+        init,
+        payer = payer,
+        space = Market::LEN,
+        seeds = [b"listing", pool.key().as_ref(), curve_state.token_mint.as_ref()],
+        bump
+    )]
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DelistMarket<'info> {
+    #[account(mut, has_one = admin @ CustomError::Unauthorized)]
+    pub pool: Account<'info, PoolState>,
+    #[account(mut, close = admin, has_one = pool)]
+    pub market: Account<'info, Market>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimProtocolFees<'info> {
+    #[account(mut, has_one = admin @ CustomError::Unauthorized)]
+    pub pool: Account<'info, PoolState>,
+    #[account(mut, address = pool.token_account)]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub admin_token_account: Account<'info, TokenAccount>,
+    pub admin: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFunding<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+}
+
+#[derive(Accounts)]
+pub struct GetPoolNav<'info> {
+    pub pool: Account<'info, PoolState>,
+}
+
+#[derive(Accounts)]
+pub struct AutoDeleverage<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, PoolState>,
+    #[account(mut, has_one = pool)]
+    pub market: Account<'info, Market>,
+    #[account(mut, has_one = market)]
+    pub position: Account<'info, PositionState>,
+    /// CHECK: Receives the position rent on a full close
+    #[account(mut, address = position.trader)]
+    pub trader: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::mint = pool.collateral_mint,
+        token::authority = position.trader
+    )]
+    pub trader_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.token_account)]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.insurance_vault)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA signer for the pool vault
+    #[account(seeds = [b"pool_authority", pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+    /// CHECK: Pyth Price Feed Account, bound to the market
+    #[account(address = market.oracle @ CustomError::InvalidOracle)]
+    pub price_feed: AccountInfo<'info>,
+    #[account(constraint = pool.adl_keepers.contains(&keeper.key()) @ CustomError::Unauthorized)]
+    pub keeper: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct FundInsurance<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, PoolState>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut, address = pool.insurance_vault)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+    pub user: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePool<'info> {
+    #[account(mut, has_one = admin @ CustomError::Unauthorized)]
+    pub pool: Account<'info, PoolState>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMarket<'info> {
+    #[account(has_one = admin @ CustomError::Unauthorized)]
+    pub pool: Account<'info, PoolState>,
+    #[account(mut, has_one = pool)]
+    pub market: Account<'info, Market>,
+    pub admin: Signer<'info>,
+}
+
+// --- Data Structures ---
+
+#[account]
+pub struct PoolState {
+    pub admin: Pubkey,
+    pub total_liquidity: u64,
+    pub admin_fee_bps: u64,
+    pub collateral_mint: Pubkey,
+    pub token_account: Pubkey, // Vault owned by the pool authority PDA
+    pub authority_bump: u8,
+    pub lp_mint: Pubkey, // LP shares, minted by the pool authority PDA
+    pub market_count: u64, // Markets that must be priced to compute NAV
+    pub listed_market_count: u64, // Of which permissionless fair-launch listings
+    pub protocol_fees: u64, // Admin share of fees, held in the vault until claimed
+    pub insurance_fund: u64, // Held in the insurance vault, outside LP NAV, to absorb bad debt
+    pub insurance_vault: Pubkey, // Token account holding the insurance fund
+    pub insurance_fee_bps: u64, // Share of the LP portion of fees sent to the fund
+    pub insurance_liquidation_bps: u64, // Share of liquidation leftovers sent to the fund
+    pub insurance_drawn: u64, // Lifetime bad debt covered by the fund
+    pub uncovered_bad_debt: u64, // Lifetime bad debt the fund could not cover
+    pub adl_threshold_bps: u64, // Auto-deleveraging opens when NAV < this share of liquidity
+    pub adl_keepers: [Pubkey; MAX_ADL_KEEPERS], // Signers allowed to auto-deleverage
+    pub withdrawal_cooldown_secs: u64, // Delay between requesting and redeeming LP shares
+    pub withdrawal_epoch_secs: u64,
+    pub max_epoch_withdrawal_bps: u64, // Redemptions per epoch, as bps of NAV
+    pub epoch_start: i64,
+    pub epoch_withdrawn: u64,
+}
+
+impl PoolState {
+    /// Starts a new withdrawal epoch if the current one has ended and returns what is
+    /// left of its cap at `nav`
+    fn epoch_allowance(&mut self, nav: u64, now: i64) -> Result<u64> {
+        let epoch_end = self
+            .epoch_start
+            .checked_add(self.withdrawal_epoch_secs as i64)
+            .ok_or(CustomError::MathOverflow)?;
+        if now >= epoch_end {
+            self.epoch_start = now - (now - self.epoch_start) % self.withdrawal_epoch_secs as i64;
+            self.epoch_withdrawn = 0;
+        }
+        Ok(bps_of(nav, self.max_epoch_withdrawal_bps)?.saturating_sub(self.epoch_withdrawn))
+    }
+}
+
+#[account]
+pub struct WithdrawalRequest {
+    pub owner: Pubkey,
+    pub pool: Pubkey,
+    pub shares: u64, // Still queued, held in the request's escrow
+    pub requested_at: i64,
+    pub unlock_at: i64,
+    pub bump: u8,
+}
+
+impl WithdrawalRequest {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 8 + 1;
+}
+
+/// Snapshot returned by `get_insurance_fund`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct InsuranceFundHealth {
+    pub balance: u64,
+    pub coverage_bps: u64, // Fund balance relative to LP liquidity
+    pub total_drawn: u64,
+    pub uncovered_bad_debt: u64,
+}
+
+#[account]
+pub struct Market {
+    pub pool: Pubkey,
+    pub symbol: String, // "SOL-USD", max MAX_SYMBOL_LEN bytes; unique per pool
+    pub status: MarketStatus,
+    pub oracle_kind: OracleKind,
+    pub twap_window_secs: u64, // FairLaunchTwap only
+    pub oracle: Pubkey,
+    pub max_leverage: u64,
+    pub max_long_oi: u64,
+    pub max_short_oi: u64,
+    pub last_oi_cap_update: i64, // 0 until the first caps are set
+    pub long_oi: u64,
+    pub short_oi: u64,
+    pub long_avg_entry_price: i64,
+    pub short_avg_entry_price: i64,
+    pub open_fee_bps: u64,
+    pub close_fee_bps: u64,
+    pub borrow_fee_bps_per_hour: u64,
+    pub funding_rate_bps_per_hour: u64, // Rate at 100% skew
+    pub cumulative_funding_index: i128, // Paid per unit of long size, scaled by FUNDING_PRECISION
+    pub last_funding_time: i64,
+    pub max_price_age_secs: u64,
+    pub max_confidence_bps: u64, // Max Pyth confidence, as bps of price
+    pub margin_tier_count: u8,
+    pub margin_tiers: [MarginTier; MAX_MARGIN_TIERS], // By ascending notional
+    pub liquidation_fee_bps: u64, // Keeper bounty, as bps of position size
+    pub skew_scale: u64, // Price impact = skew^2 / (2 * skew_scale); 0 disables it
+    pub impact_fee_balance: u64, // Net impact fees collected, the budget for rebates
+    pub bump: u8,
+}
+
+/// Active: all trading. ReduceOnly: no new exposure. Paused: only keepers
+/// (liquidation, ADL) may touch positions.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MarketStatus {
+    Active,
+    ReduceOnly,
+    Paused,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Debug)]
+pub struct MarginTier {
+    pub max_notional: u64, // Upper bound of position size for this tier
+    pub initial_margin_bps: u64,
+    pub maintenance_margin_bps: u64,
+}
+
+impl MarginTier {
+    pub const LEN: usize = 8 + 8 + 8;
+}
+
+/// Pyth: `oracle` is a Pyth price feed. FairLaunchTwap: `oracle` is a fair_launch
+/// `Observations` account and the price is its TWAP over `twap_window_secs`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OracleKind {
+    Pyth,
+    FairLaunchTwap,
+}
+
+impl Market {
+    pub const LEN: usize = 8 + 32 + (4 + MAX_SYMBOL_LEN) + 1 + 1 + 8 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 16 + 8 + 8 + 8 + 1 + MarginTier::LEN * MAX_MARGIN_TIERS + 8 + 8 + 8 + 1;
+
+    /// Validates and stores margin tiers: 1..=MAX_MARGIN_TIERS tiers with increasing
+    /// notional bounds, non-decreasing margins and maintenance <= initial <= 100%
+    fn set_margin_tiers(&mut self, tiers: &[MarginTier]) -> Result<()> {
+        require!(
+            !tiers.is_empty() && tiers.len() <= MAX_MARGIN_TIERS,
+            CustomError::InvalidMarginTiers
+        );
+        for (i, tier) in tiers.iter().enumerate() {
+            require!(
+                tier.initial_margin_bps <= 10_000
+                    && tier.maintenance_margin_bps > 0
+                    && tier.maintenance_margin_bps <= tier.initial_margin_bps,
+                CustomError::InvalidMarginTiers
+            );
+            if i > 0 {
+                let prev = &tiers[i - 1];
+                require!(
+                    tier.max_notional > prev.max_notional
+                        && tier.initial_margin_bps >= prev.initial_margin_bps
+                        && tier.maintenance_margin_bps >= prev.maintenance_margin_bps,
+                    CustomError::InvalidMarginTiers
+                );
+            }
+        }
+
+        self.margin_tiers = [MarginTier::default(); MAX_MARGIN_TIERS];
+        self.margin_tiers[..tiers.len()].copy_from_slice(tiers);
+        self.margin_tier_count = tiers.len() as u8;
+        Ok(())
+    }
+
+    /// Tier covering a position of `notional` size (the last tier is open-ended)
+    fn margin_tier(&self, notional: u64) -> MarginTier {
+        let tiers = &self.margin_tiers[..self.margin_tier_count as usize];
+        *tiers
+            .iter()
+            .find(|tier| notional <= tier.max_notional)
+            .unwrap_or(&tiers[tiers.len() - 1])
+    }
+
+    fn initial_margin(&self, notional: u64) -> Result<u64> {
+        bps_of(notional, self.margin_tier(notional).initial_margin_bps)
+    }
+
+    fn maintenance_margin(&self, notional: u64) -> Result<u64> {
+        bps_of(notional, self.margin_tier(notional).maintenance_margin_bps)
+    }
+
+    /// Fails if `position`, marked at `price`, is below maintenance margin.
+    /// A closed position always passes.
+    fn check_maintenance(&self, position: &PositionState, price: i64) -> Result<()> {
+        if position.size == 0 {
+            return Ok(());
+        }
+        let pnl = position_pnl(position.is_long, position.size, position.entry_price, price)?;
+        let equity = (position.collateral as i128) + (pnl as i128);
+        require!(
+            equity >= self.maintenance_margin(position.size)? as i128,
+            CustomError::InsufficientMargin
+        );
+        Ok(())
+    }
+
+    /// Checks leverage, tiered initial margin and OI limits for adding `collateral` at
+    /// `leverage` to a position currently holding `size`/`position_collateral`.
+    /// Returns the notional size added.
+    fn validate_open(
+        &self,
+        total_liquidity: u64,
+        is_long: bool,
+        size: u64,
+        position_collateral: u64,
+        collateral: u64,
+        leverage: u64,
+    ) -> Result<u64> {
+        require!(
+            leverage >= 1 && leverage <= self.max_leverage,
+            CustomError::MaxLeverageExceeded
+        );
+
+        // Initial margin is tiered on the resulting notional (against posted collateral),
+        // so big positions get less leverage
+        let size_delta = collateral
+            .checked_mul(leverage)
+            .ok_or(CustomError::MathOverflow)?;
+        let notional = size.checked_add(size_delta).ok_or(CustomError::MathOverflow)?;
+        require!(
+            position_collateral.saturating_add(collateral) >= self.initial_margin(notional)?,
+            CustomError::InsufficientMargin
+        );
+
+        // Golden Ratio Check (Crucial)
+        // New OI may exceed neither the side's cap nor (in total) 50% of Liquidity
+        let (side_oi, side_cap) = if is_long {
+            (self.long_oi, self.max_long_oi)
+        } else {
+            (self.short_oi, self.max_short_oi)
+        };
+        require!(
+            side_oi.checked_add(size_delta).ok_or(CustomError::MathOverflow)? <= side_cap,
+            CustomError::OiCapExceeded
+        );
+        let new_oi = self
+            .long_oi
+            .checked_add(self.short_oi)
+            .and_then(|oi| oi.checked_add(size_delta))
+            .ok_or(CustomError::MathOverflow)?;
+        require!(
+            new_oi <= total_liquidity / 2,
+            CustomError::GoldenRatioBreached
+        );
+        Ok(size_delta)
+    }
+
+    fn side_mut(&mut self, is_long: bool) -> (&mut u64, &mut i64) {
+        if is_long {
+            (&mut self.long_oi, &mut self.long_avg_entry_price)
+        } else {
+            (&mut self.short_oi, &mut self.short_avg_entry_price)
+        }
+    }
+
+    fn add_open_interest(&mut self, is_long: bool, size: u64, price: i64) -> Result<()> {
+        let (oi, avg) = self.side_mut(is_long);
+        *avg = average_entry_price(*oi, *avg, size, price, true)?;
+        *oi = oi.checked_add(size).ok_or(CustomError::MathOverflow)?;
+        Ok(())
+    }
+
+    fn remove_open_interest(&mut self, is_long: bool, size: u64, entry_price: i64) -> Result<()> {
+        let (oi, avg) = self.side_mut(is_long);
+        *avg = average_entry_price(*oi, *avg, size, entry_price, false)?;
+        *oi = oi.saturating_sub(size);
+        Ok(())
+    }
+
+    /// Signed price impact of a trade moving the skew (long - short OI):
+    /// (skew_after^2 - skew_before^2) / (2 * skew_scale).
+    /// Positive when the trade widens the skew (a fee), negative when it narrows it (a rebate).
+    fn price_impact(&self, is_long: bool, size: u64, opening: bool) -> Result<i64> {
+        if self.skew_scale == 0 {
+            return Ok(0);
+        }
+        let before = (self.long_oi as i128) - (self.short_oi as i128);
+        let delta = if is_long == opening { size as i128 } else { -(size as i128) };
+        let after = before + delta;
+        let impact = after
+            .checked_mul(after)
+            .zip(before.checked_mul(before))
+            .map(|(a, b)| a - b)
+            .ok_or(CustomError::MathOverflow)?
+            / (2 * self.skew_scale as i128);
+        i64::try_from(impact).map_err(|_| error!(CustomError::MathOverflow))
+    }
+
+    /// Accrues funding since the last update. Longs pay shorts when long OI dominates
+    /// and vice versa, scaled by the skew (long - short) / (long + short).
+    fn update_funding(&mut self, market: Pubkey, now: i64) -> Result<()> {
+        let elapsed = now.saturating_sub(self.last_funding_time);
+        if elapsed <= 0 {
+            return Ok(());
+        }
+
+        let total_oi = (self.long_oi as i128) + (self.short_oi as i128);
+        if total_oi > 0 {
+            let skew = (self.long_oi as i128) - (self.short_oi as i128);
+            let delta = FUNDING_PRECISION
+                .checked_mul(self.funding_rate_bps_per_hour as i128)
+                .and_then(|d| d.checked_mul(skew))
+                .and_then(|d| d.checked_mul(elapsed as i128))
+                .ok_or(CustomError::MathOverflow)?
+                / (total_oi * 3_600 * 10_000);
+            self.cumulative_funding_index = self
+                .cumulative_funding_index
+                .checked_add(delta)
+                .ok_or(CustomError::MathOverflow)?;
+        }
+        self.last_funding_time = now;
+
+        emit!(FundingUpdated {
+            market,
+            symbol: self.symbol.clone(),
+            cumulative_funding_index: self.cumulative_funding_index,
+            long_oi: self.long_oi,
+            short_oi: self.short_oi,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    /// Aggregate unrealized PnL of all traders in this market at `price`
+    fn unrealized_trader_pnl(&self, price: i64) -> Result<i64> {
+        let mut pnl: i64 = 0;
+        if self.long_oi > 0 {
+            pnl += position_pnl(true, self.long_oi, self.long_avg_entry_price, price)?;
+        }
+        if self.short_oi > 0 {
+            pnl += position_pnl(false, self.short_oi, self.short_avg_entry_price, price)?;
+        }
+        Ok(pnl)
+    }
+
+    /// Unrealized PnL counting only the sides in profit, for marking at a price
+    /// LPs should not profit from
+    fn unrealized_trader_profit(&self, price: i64) -> Result<i64> {
+        let mut pnl: i64 = 0;
+        if self.long_oi > 0 {
+            pnl += position_pnl(true, self.long_oi, self.long_avg_entry_price, price)?.max(0);
+        }
+        if self.short_oi > 0 {
+            pnl += position_pnl(false, self.short_oi, self.short_avg_entry_price, price)?.max(0);
+        }
+        Ok(pnl)
+    }
+}
+
+#[account]
+pub struct PositionState {
+    pub trader: Pubkey,
+    pub market: Pubkey,
+    pub is_long: bool,
+    pub bump: u8, // PDA of (trader, market, side)
+    pub size: u64,
+    pub collateral: u64,
+    pub entry_price: i64,
+    pub timestamp: i64,
+    pub entry_funding_index: i128,
+    pub last_borrow_fee_time: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TriggerKind {
+    TakeProfit,
+    StopLoss,
+}
+
+#[account]
+pub struct TriggerOrder {
+    pub position: Pubkey,
+    pub trader: Pubkey,
+    pub position_opened_at: i64, // Ties the order to one lifetime of the position PDA
+    pub kind: TriggerKind,
+    pub trigger_price: i64,
+    pub size: u64,       // 0 = close the whole position
+    pub keeper_fee: u64, // Lamports escrowed in this account for the executing keeper
+    pub bump: u8,
+}
+
+#[account]
+pub struct LimitOrder {
+    pub trader: Pubkey,
+    pub market: Pubkey,
+    pub order_id: u64,
+    pub is_long: bool,
+    pub collateral: u64, // Held in the order's escrow token account
+    pub leverage: u64,
+    pub limit_price: i64,
+    pub expiry: i64,
+    pub keeper_fee: u64, // Lamports escrowed in this account for the executing keeper
+    pub bump: u8,
+}
+
+impl LimitOrder {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 1 + 8 + 8 + 8 + 8 + 8 + 1;
+}
+
+impl TriggerOrder {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 1 + 8 + 8 + 8 + 1;
+}
+
+impl PositionState {
+    pub const LEN: usize = 8 + 32 + 32 + 1 + 1 + 8 + 8 + 8 + 8 + 16 + 8;
+}
+
+// --- Events ---
+
+#[event]
+pub struct LiquidityAdded {
+    pub provider: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub nav: u64,
+}
+
+#[event]
+pub struct WithdrawalRequested {
+    pub provider: Pubkey,
+    pub shares: u64,
+    pub unlock_at: i64,
+}
+
+#[event]
+pub struct LiquidityRemoved {
+    pub provider: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub nav: u64,
+}
+
+#[event]
+pub struct PositionClosed {
+    pub position: Pubkey,
+    pub trader: Pubkey,
+    pub market: Pubkey,
+    pub is_long: bool,
+    pub size: u64,
+    pub entry_price: i64,
+    pub exit_price: i64,
+    pub pnl: i64,
+    pub funding: i64,
+    pub fees: u64,
+    pub payout: u64,
+}
+
+#[event]
+pub struct MarketListed {
+    pub market: Pubkey,
+    pub token_mint: Pubkey,
+    pub symbol: String,
+    pub twap: u128,
+    pub max_long_oi: u64,
+    pub max_short_oi: u64,
+    pub lister: Pubkey,
+}
+
+#[event]
+pub struct MarketDelisted {
+    pub market: Pubkey,
+    pub symbol: String,
+}
+
+#[event]
+pub struct FundingUpdated {
+    pub market: Pubkey,
+    pub symbol: String,
+    pub cumulative_funding_index: i128,
+    pub long_oi: u64,
+    pub short_oi: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CollateralAdded {
+    pub position: Pubkey,
+    pub trader: Pubkey,
+    pub amount: u64,
+    pub collateral: u64,
+}
+
+#[event]
+pub struct CollateralRemoved {
+    pub position: Pubkey,
+    pub trader: Pubkey,
+    pub amount: u64,
+    pub collateral: u64,
+}
+
+#[event]
+pub struct PositionDecreased {
+    pub position: Pubkey,
+    pub trader: Pubkey,
+    pub market: Pubkey,
+    pub is_long: bool,
+    pub size_delta: u64,
+    pub remaining_size: u64,
+    pub price: i64,
+    pub pnl: i64,
+    pub fees: u64,
+    pub payout: u64,
+}
+
+#[event]
+pub struct TriggerOrderExecuted {
+    pub order: Pubkey,
+    pub position: Pubkey,
+    pub trader: Pubkey,
+    pub keeper: Pubkey,
+    pub kind: TriggerKind,
+    pub trigger_price: i64,
+    pub price: i64,
+    pub size_delta: u64,
+    pub pnl: i64,
+    pub payout: u64,
+    pub keeper_fee: u64,
+}
+
+#[event]
+pub struct LimitOrderExecuted {
+    pub order: Pubkey,
+    pub position: Pubkey,
+    pub trader: Pubkey,
+    pub keeper: Pubkey,
+    pub market: Pubkey,
+    pub is_long: bool,
+    pub limit_price: i64,
+    pub price: i64,
+    pub size: u64,
+    pub collateral: u64,
+    pub keeper_fee: u64,
+}
+
+#[event]
+pub struct PositionLiquidated {
+    pub position: Pubkey,
+    pub trader: Pubkey,
+    pub liquidator: Pubkey,
+    pub market: Pubkey,
+    pub is_long: bool,
+    pub size: u64,
+    pub collateral: u64,
+    pub price: i64,
+    pub pnl: i64,
+    pub bounty: u64,
+    pub to_pool: u64,
+    pub to_insurance: u64,
+}
+
+#[event]
+pub struct PositionDeleveraged {
+    pub position: Pubkey,
+    pub trader: Pubkey,
+    pub keeper: Pubkey,
+    pub market: Pubkey,
+    pub is_long: bool,
+    pub size_delta: u64,
+    pub remaining_size: u64,
+    pub price: i64,
+    pub pnl: i64,
+    pub payout: u64,
+    pub score: u128,
+    pub pool_nav: u64,
+}
+
+#[event]
+pub struct InsuranceFundDrawn {
+    pub trader: Pubkey,
+    pub market: Pubkey,
+    pub is_long: bool,
+    pub shortfall: u64,
+    pub covered: u64,
+    pub uncovered: u64,
+    pub balance: u64,
+}
+
+// --- Errors ---
+
+#[error_code]
+pub enum CustomError {
+    #[msg("Unauthorized access")]
+    Unauthorized,
+    #[msg("Max leverage exceeded (Max 50x)")]
+    MaxLeverageExceeded,
+    #[msg("Golden Ratio Breached: Max OI Reached")]
+    GoldenRatioBreached,
+    #[msg("Invalid oracle account or price")]
+    InvalidOracle,
+    #[msg("Oracle price is stale")]
+    StaleOraclePrice,
+    #[msg("Oracle confidence interval too wide")]
+    OracleConfidenceTooWide,
+    #[msg("Math overflow")]
+    MathOverflow,
+    #[msg("Position is above maintenance margin")]
+    PositionNotLiquidatable,
+    #[msg("Market symbol is empty or too long")]
+    InvalidSymbol,
+    #[msg("Invalid parameter")]
+    InvalidParameter,
+    #[msg("Amount must be greater than zero")]
+    InvalidAmount,
+    #[msg("Insufficient pool liquidity")]
+    InsufficientLiquidity,
+    #[msg("Remaining accounts must list every pool market with its oracle")]
+    InvalidMarketAccounts,
+    #[msg("Collateral does not cover fees")]
+    InsufficientCollateral,
+    #[msg("Position would fall below the margin requirement")]
+    InsufficientMargin,
+    #[msg("Oracle price has not reached the trigger")]
+    TriggerNotReached,
+    #[msg("Trigger order belongs to a closed position")]
+    StaleTriggerOrder,
+    #[msg("Market is not open for new positions")]
+    MarketNotActive,
+    #[msg("Market is paused")]
+    MarketPaused,
+    #[msg("Open interest cap for this side reached")]
+    OiCapExceeded,
+    #[msg("OI cap change exceeds the per-update step limit")]
+    OiCapStepTooLarge,
+    #[msg("OI caps were updated too recently")]
+    OiCapUpdateTooSoon,
+    #[msg("Margin tiers are invalid")]
+    InvalidMarginTiers,
+    #[msg("Withdrawal is still in its cooldown")]
+    WithdrawalLocked,
+    #[msg("Epoch withdrawal cap reached")]
+    EpochWithdrawalCapReached,
+    #[msg("Token has not graduated from its bonding curve")]
+    NotGraduated,
+    #[msg("Pool market limit reached")]
+    TooManyMarkets,
+    #[msg("Limit order has expired")]
+    LimitOrderExpired,
+    #[msg("Pool NAV is above the auto-deleveraging threshold")]
+    AdlNotActive,
+    #[msg("Position is not the top-ranked profitable position")]
+    NotDeleveragable,
+    #[msg("Pool NAV is zero while LP shares are outstanding")]
+    PoolInsolvent,
+    #[msg("Market still has open interest")]
+    MarketHasOpenInterest,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> PoolState {
+        PoolState {
+            admin: Pubkey::default(),
+            total_liquidity: 0,
+            admin_fee_bps: 0,
+            collateral_mint: Pubkey::default(),
+            token_account: Pubkey::default(),
+            authority_bump: 0,
+            lp_mint: Pubkey::default(),
+            market_count: 0,
+            listed_market_count: 0,
+            protocol_fees: 0,
+            insurance_fund: 0,
+            insurance_vault: Pubkey::default(),
+            insurance_fee_bps: 0,
+            insurance_liquidation_bps: 0,
+            insurance_drawn: 0,
+            uncovered_bad_debt: 0,
+            adl_threshold_bps: 0,
+            adl_keepers: [Pubkey::default(); MAX_ADL_KEEPERS],
+            withdrawal_cooldown_secs: 0,
+            withdrawal_epoch_secs: 100,
+            max_epoch_withdrawal_bps: 1000,
+            epoch_start: 0,
+            epoch_withdrawn: 0,
+        }
+    }
+
+    fn market() -> Market {
+        Market {
+            pool: Pubkey::default(),
+            symbol: "SOL-PERP".to_string(),
+            status: MarketStatus::Active,
+            oracle_kind: OracleKind::Pyth,
+            twap_window_secs: 0,
+            oracle: Pubkey::default(),
+            max_leverage: 10,
+            max_long_oi: 0,
+            max_short_oi: 0,
+            last_oi_cap_update: 0,
+            long_oi: 0,
+            short_oi: 0,
+            long_avg_entry_price: 0,
+            short_avg_entry_price: 0,
+            open_fee_bps: 0,
+            close_fee_bps: 0,
+            borrow_fee_bps_per_hour: 0,
+            funding_rate_bps_per_hour: 0,
+            cumulative_funding_index: 0,
+            last_funding_time: 0,
+            max_price_age_secs: 0,
+            max_confidence_bps: 0,
+            margin_tier_count: 0,
+            margin_tiers: [MarginTier::default(); MAX_MARGIN_TIERS],
+            liquidation_fee_bps: 0,
+            skew_scale: 0,
+            impact_fee_balance: 0,
+            bump: 0,
+        }
+    }
+
+    fn position() -> PositionState {
+        PositionState {
+            trader: Pubkey::default(),
+            market: Pubkey::default(),
+            is_long: true,
+            bump: 0,
+            size: 0,
+            collateral: 0,
+            entry_price: 0,
+            timestamp: 0,
+            entry_funding_index: 0,
+            last_borrow_fee_time: 0,
+        }
+    }
+
+    fn tier(max_notional: u64, initial_margin_bps: u64, maintenance_margin_bps: u64) -> MarginTier {
+        MarginTier { max_notional, initial_margin_bps, maintenance_margin_bps }
+    }
+
+    #[test]
+    fn pnl_is_size_times_relative_move() {
+        assert_eq!(position_pnl(true, 1_000, 100, 110).unwrap(), 100);
+        assert_eq!(position_pnl(false, 1_000, 100, 110).unwrap(), -100);
+        assert_eq!(position_pnl(true, 1_000, 100, 95).unwrap(), -50);
+        assert!(position_pnl(true, 1_000, 0, 95).is_err());
+    }
+
+    #[test]
+    fn settle_pnl_pays_profit_up_to_the_pool() {
+        let mut pool = pool();
+        pool.total_liquidity = 500;
+        assert_eq!(settle_pnl(&mut pool, &position(), 100, 200).unwrap(), 300);
+        assert_eq!(pool.total_liquidity, 300);
+
+        // Profit is capped at what LPs hold
+        assert_eq!(settle_pnl(&mut pool, &position(), 100, 1_000).unwrap(), 400);
+        assert_eq!(pool.total_liquidity, 0);
+    }
+
+    #[test]
+    fn settle_pnl_books_losses_to_the_pool() {
+        let mut pool = pool();
+        pool.total_liquidity = 500;
+        assert_eq!(settle_pnl(&mut pool, &position(), 100, -30).unwrap(), 70);
+        assert_eq!(pool.total_liquidity, 530);
+
+        // Losses stop at the collateral
+        assert_eq!(settle_pnl(&mut pool, &position(), 100, -150).unwrap(), 0);
+        assert_eq!(pool.total_liquidity, 630);
+        assert_eq!(pool.uncovered_bad_debt, 50);
+    }
+
+    #[test]
+    fn shares_price_deposits_at_nav() {
+        // The first deposit mints 1:1
+        assert_eq!(shares_for_deposit(1_000, 0, 0).unwrap(), 1_000);
+        assert_eq!(shares_for_deposit(1_000, 0, 500).unwrap(), 1_000);
+        // Shares worth 2 each
+        assert_eq!(shares_for_deposit(1_000, 500, 1_000).unwrap(), 500);
+        assert_eq!(shares_for_deposit(3, 500, 1_000).unwrap(), 1);
+        assert_eq!(
+            shares_for_deposit(1_000, 500, 0).unwrap_err(),
+            CustomError::PoolInsolvent.into()
+        );
+    }
+
+    #[test]
+    fn shares_redeem_pro_rata_of_nav() {
+        assert_eq!(amount_for_shares(250, 500, 1_000).unwrap(), 500);
+        assert_eq!(amount_for_shares(500, 500, 1_000).unwrap(), 1_000);
+        assert_eq!(amount_for_shares(1, 3, 10).unwrap(), 3);
+        assert!(amount_for_shares(501, 500, 1_000).is_err());
+        assert!(amount_for_shares(1, 0, 1_000).is_err());
+    }
+
+    #[test]
+    fn average_entry_is_harmonic_in_size() {
+        assert_eq!(average_entry_price(0, 0, 100, 100, true).unwrap(), 100);
+        assert_eq!(average_entry_price(100, 100, 100, 100, true).unwrap(), 100);
+        // 200 / (100/100 + 100/300)
+        assert_eq!(average_entry_price(100, 100, 100, 300, true).unwrap(), 150);
+        // Removing the second fill restores the first entry
+        assert_eq!(average_entry_price(200, 150, 100, 300, false).unwrap(), 100);
+        assert_eq!(average_entry_price(200, 150, 200, 300, false).unwrap(), 0);
+    }
+
+    #[test]
+    fn aggregate_pnl_matches_the_positions() {
+        let mut market = market();
+        market.long_oi = 200;
+        market.long_avg_entry_price = 150;
+        market.short_oi = 100;
+        market.short_avg_entry_price = 200;
+        // Longs 100@100 + 100@300 and a short 100@200, all marked at 300
+        let longs = position_pnl(true, 100, 100, 300).unwrap() + position_pnl(true, 100, 300, 300).unwrap();
+        assert_eq!(market.unrealized_trader_pnl(300).unwrap(), longs - 50);
+    }
+
+    #[test]
+    fn nav_subtracts_trader_pnl_and_floors_at_zero() {
+        let mark = |trader_pnl| MarketMark { market: Pubkey::default(), price: 1, trader_pnl };
+        assert_eq!(nav_from_marks(1_000, &[]).unwrap(), 1_000);
+        assert_eq!(nav_from_marks(1_000, &[mark(300), mark(-100)]).unwrap(), 800);
+        assert_eq!(nav_from_marks(1_000, &[mark(2_000)]).unwrap(), 0);
+    }
+
+    #[test]
+    fn funding_accrues_on_skew_and_charges_the_heavy_side() {
+        let mut market = market();
+        market.funding_rate_bps_per_hour = 10;
+        market.long_oi = 3_000_000;
+        market.short_oi = 1_000_000;
+        market.update_funding(Pubkey::default(), 3_600).unwrap();
+        // 10 bps/h scaled by a 50% skew
+        assert_eq!(market.cumulative_funding_index, FUNDING_PRECISION / 2_000);
+        assert_eq!(market.last_funding_time, 3_600);
+
+        let index = market.cumulative_funding_index;
+        assert_eq!(position_funding(true, 1_000_000, 0, index).unwrap(), 500);
+        assert_eq!(position_funding(false, 1_000_000, 0, index).unwrap(), -500);
+        // Only the index change since entry is owed
+        assert_eq!(position_funding(true, 1_000_000, index, index).unwrap(), 0);
+
+        // No time elapsed, no accrual
+        market.update_funding(Pubkey::default(), 3_600).unwrap();
+        assert_eq!(market.cumulative_funding_index, index);
+    }
+
+    #[test]
+    fn borrow_fee_accrues_per_hour() {
+        assert_eq!(borrow_fee(1_000_000, 10, 0, 3_600).unwrap(), 1_000);
+        assert_eq!(borrow_fee(1_000_000, 10, 0, 1_800).unwrap(), 500);
+        // Clock going backwards charges nothing
+        assert_eq!(borrow_fee(1_000_000, 10, 3_600, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn fees_split_between_protocol_insurance_and_lps() {
+        let mut pool = pool();
+        pool.admin_fee_bps = 2_000;
+        pool.insurance_fee_bps = 2_500;
+        distribute_fee(&mut pool, 1_000).unwrap();
+        // 20% to the protocol, then a quarter of the LP part to insurance
+        assert_eq!(pool.protocol_fees, 200);
+        assert_eq!(pool.insurance_fund, 200);
+        assert_eq!(pool.total_liquidity, 600);
+    }
+
+    #[test]
+    fn accruals_settle_against_collateral_and_reset() {
+        let mut pool = pool();
+        pool.total_liquidity = 100_000;
+        let mut market = market();
+        market.cumulative_funding_index = FUNDING_PRECISION / 2_000;
+        market.borrow_fee_bps_per_hour = 10;
+        let mut position = position();
+        position.size = 1_000_000;
+        position.collateral = 10_000;
+
+        assert_eq!(settle_accruals(&mut pool, &market, &mut position, 3_600).unwrap(), (500, 1_000));
+        assert_eq!(position.collateral, 8_500);
+        assert_eq!(pool.total_liquidity, 101_500);
+        assert_eq!(position.entry_funding_index, market.cumulative_funding_index);
+        assert_eq!(position.last_borrow_fee_time, 3_600);
+
+        // Settling again right away owes nothing
+        assert_eq!(settle_accruals(&mut pool, &market, &mut position, 3_600).unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn accruals_pay_funding_to_the_light_side() {
+        let mut pool = pool();
+        pool.total_liquidity = 100_000;
+        let mut market = market();
+        market.cumulative_funding_index = FUNDING_PRECISION / 2_000;
+        let mut position = position();
+        position.is_long = false;
+        position.size = 1_000_000;
+        position.collateral = 10_000;
+
+        assert_eq!(settle_accruals(&mut pool, &market, &mut position, 0).unwrap(), (-500, 0));
+        assert_eq!(position.collateral, 10_500);
+        assert_eq!(pool.total_liquidity, 99_500);
+    }
+
+    #[test]
+    fn triggers_fire_in_the_right_direction() {
+        assert!(trigger_crossed(TriggerKind::TakeProfit, true, 110, 110));
+        assert!(!trigger_crossed(TriggerKind::TakeProfit, true, 110, 109));
+        assert!(trigger_crossed(TriggerKind::StopLoss, true, 90, 89));
+        assert!(!trigger_crossed(TriggerKind::StopLoss, true, 90, 91));
+        assert!(trigger_crossed(TriggerKind::TakeProfit, false, 90, 90));
+        assert!(!trigger_crossed(TriggerKind::TakeProfit, false, 90, 91));
+        assert!(trigger_crossed(TriggerKind::StopLoss, false, 110, 111));
+        assert!(!trigger_crossed(TriggerKind::StopLoss, false, 110, 109));
+    }
+
+    #[test]
+    fn reduce_realizes_pnl_on_a_pro_rata_slice() {
+        let mut pool = pool();
+        pool.total_liquidity = 100_000;
+        let mut market = market();
+        market.close_fee_bps = 10;
+        market.long_oi = 10_000;
+        market.long_avg_entry_price = 100;
+        let mut position = position();
+        position.size = 10_000;
+        position.collateral = 1_000;
+        position.entry_price = 100;
+
+        // Close half at +10%: 500 collateral + 500 profit - 5 close fee
+        let settlement = reduce_position(&mut pool, &mut market, &mut position, 5_000, 110, 0, true).unwrap();
+        assert_eq!((settlement.pnl, settlement.fees, settlement.payout), (500, 5, 995));
+        assert_eq!((position.size, position.collateral), (5_000, 500));
+        assert_eq!(market.long_oi, 5_000);
+        assert_eq!(pool.total_liquidity, 99_505);
+
+        assert!(reduce_position(&mut pool, &mut market, &mut position, 5_001, 110, 0, true).is_err());
+    }
+
+    #[test]
+    fn maintenance_check_covers_the_remaining_position() {
+        let mut market = market();
+        market.set_margin_tiers(&[tier(u64::MAX, 1000, 500)]).unwrap();
+        let mut position = position();
+        position.size = 10_000;
+        position.collateral = 1_000;
+        position.entry_price = 100;
+
+        // Maintenance is 500: equity 1_000 - 500 at -5% just holds
+        market.check_maintenance(&position, 95).unwrap();
+        assert_eq!(
+            market.check_maintenance(&position, 94).unwrap_err(),
+            CustomError::InsufficientMargin.into()
+        );
+        position.size = 0;
+        market.check_maintenance(&position, 1).unwrap();
+    }
+
+    #[test]
+    fn validate_open_checks_leverage_margin_and_caps() {
+        let mut market = market();
+        market.set_margin_tiers(&[tier(1_000, 1000, 500), tier(u64::MAX, 5000, 2500)]).unwrap();
+        market.max_long_oi = 2_000;
+
+        assert_eq!(market.validate_open(10_000, true, 0, 0, 100, 10).unwrap(), 1_000);
+        assert!(market.validate_open(10_000, true, 0, 0, 100, 11).is_err());
+        // 10x on a notional in the 50% tier
+        assert!(market.validate_open(10_000, true, 0, 0, 101, 10).is_err());
+        // Side cap, then the pool-wide 50% ratio
+        assert_eq!(
+            market.validate_open(10_000, true, 0, 0, 1_100, 2).unwrap_err(),
+            CustomError::OiCapExceeded.into()
+        );
+        assert_eq!(
+            market.validate_open(1_000, true, 0, 0, 100, 10).unwrap_err(),
+            CustomError::GoldenRatioBreached.into()
+        );
+    }
+
+    #[test]
+    fn bad_debt_draws_insurance_before_lps() {
+        let mut pool = pool();
+        pool.total_liquidity = 1_000;
+        pool.insurance_fund = 30;
+        // 150 loss on 100 collateral: 30 of the 50 shortfall comes from insurance
+        assert_eq!(settle_pnl(&mut pool, &position(), 100, -150).unwrap(), 0);
+        assert_eq!(pool.total_liquidity, 1_130);
+        assert_eq!(pool.insurance_fund, 0);
+        assert_eq!(pool.insurance_drawn, 30);
+        assert_eq!(pool.uncovered_bad_debt, 20);
+    }
+
+    #[test]
+    fn bad_debt_with_empty_insurance_stays_with_lps() {
+        let mut pool = pool();
+        pool.total_liquidity = 1_000;
+        cover_bad_debt(&mut pool, &position(), 0).unwrap();
+        assert_eq!(pool.uncovered_bad_debt, 0);
+        cover_bad_debt(&mut pool, &position(), 40).unwrap();
+        assert_eq!(pool.total_liquidity, 1_000);
+        assert_eq!(pool.uncovered_bad_debt, 40);
+    }
+
+    #[test]
+    fn liquidation_pays_the_bounty_first() {
+        // 100 left: 10 bounty, the 90 penalty split half to insurance
+        assert_eq!(liquidation_split(100, 10, 5_000).unwrap(), (10, 45, 45));
+        // Bounty is capped at what is left
+        assert_eq!(liquidation_split(8, 10, 5_000).unwrap(), (8, 0, 0));
+        assert_eq!(liquidation_split(100, 0, 0).unwrap(), (0, 100, 0));
+    }
+
+    #[test]
+    fn adl_score_ranks_profit_by_leverage() {
+        assert_eq!(adl_score(-50, 1_000, 100), 0);
+        assert_eq!(adl_score(0, 1_000, 100), 0);
+        assert_eq!(adl_score(50, 1_000, 0), u128::MAX);
+        // Same PnL: the 10x position outranks the 2x one
+        assert_eq!(adl_score(50, 1_000, 100), 50_000);
+        assert_eq!(adl_score(50, 1_000, 500), 2_000);
+    }
+
+    #[test]
+    fn price_impact_charges_widening_and_rebates_narrowing() {
+        let mut market = market();
+        market.long_oi = 100;
+        assert_eq!(market.price_impact(true, 100, true).unwrap(), 0);
+
+        market.skew_scale = 1_000;
+        // Skew 100 -> 200: (200^2 - 100^2) / 2000
+        assert_eq!(market.price_impact(true, 100, true).unwrap(), 15);
+        // Opening a short or closing a long brings the skew back to zero
+        assert_eq!(market.price_impact(false, 100, true).unwrap(), -5);
+        assert_eq!(market.price_impact(true, 100, false).unwrap(), -5);
+    }
+
+    #[test]
+    fn impact_rebates_are_bounded_by_collected_fees() {
+        let mut pool = pool();
+        pool.total_liquidity = 1_000;
+        let mut market = market();
+        // Fees are capped at what the trader can pay
+        assert_eq!(settle_price_impact(&mut pool, &mut market, 15, 10).unwrap(), 10);
+        assert_eq!((market.impact_fee_balance, pool.total_liquidity), (10, 1_010));
+        assert_eq!(settle_price_impact(&mut pool, &mut market, -25, 0).unwrap(), -10);
+        assert_eq!((market.impact_fee_balance, pool.total_liquidity), (0, 1_000));
+    }
+
+    #[test]
+    fn untrusted_marks_count_only_trader_profits() {
+        let mut market = market();
+        market.long_oi = 1_000;
+        market.long_avg_entry_price = 100;
+        market.short_oi = 500;
+        market.short_avg_entry_price = 100;
+        // Longs +100, shorts -50
+        assert_eq!(market.unrealized_trader_pnl(110).unwrap(), 50);
+        assert_eq!(market.unrealized_trader_profit(110).unwrap(), 100);
+        assert_eq!(market.unrealized_trader_profit(90).unwrap(), 50);
+    }
+
+    #[test]
+    fn twap_quotes_are_trusted_only_when_fresh_and_near_spot() {
+        let mut market = market();
+        market.max_price_age_secs = LISTING_MAX_PRICE_AGE_SECS;
+        market.max_confidence_bps = 500;
+        let quote = |age_secs, divergence_bps| TwapQuote { price: 100, age_secs, divergence_bps };
+        assert!(quote(LISTING_MAX_PRICE_AGE_SECS, 500).is_trusted(&market));
+        assert!(!quote(LISTING_MAX_PRICE_AGE_SECS + 1, 0).is_trusted(&market));
+        assert!(!quote(0, 501).is_trusted(&market));
+    }
+
+    #[test]
+    fn epoch_cap_limits_and_resets_withdrawals() {
+        let mut pool = pool();
+        assert_eq!(pool.epoch_allowance(10_000, 10).unwrap(), 1_000);
+
+        pool.epoch_withdrawn = 1_000;
+        assert_eq!(pool.epoch_allowance(10_000, 99).unwrap(), 0);
+
+        // A new epoch starts on its boundary and clears the counter
+        assert_eq!(pool.epoch_allowance(10_000, 250).unwrap(), 1_000);
+        assert_eq!(pool.epoch_start, 200);
+        assert_eq!(pool.epoch_withdrawn, 0);
+    }
+
+    #[test]
+    fn withdrawal_fill_is_partial_at_the_epoch_cap() {
+        // 10 collateral per share
+        assert_eq!(withdrawal_fill(200, 1_000, 10_000, 5_000).unwrap(), (200, 2_000));
+        assert_eq!(withdrawal_fill(200, 1_000, 10_000, 1_000).unwrap(), (100, 1_000));
+        assert_eq!(
+            withdrawal_fill(200, 1_000, 10_000, 0).unwrap_err(),
+            CustomError::EpochWithdrawalCapReached.into()
+        );
+    }
+
+    #[test]
+    fn margin_tiers_apply_at_boundaries() {
+        let mut market = market();
+        market
+            .set_margin_tiers(&[tier(100, 1000, 500), tier(1_000, 2000, 1000), tier(u64::MAX, 5000, 2500)])
+            .unwrap();
+
+        // Bounds are inclusive; the next unit moves to the next tier
+        assert_eq!(market.initial_margin(100).unwrap(), 10);
+        assert_eq!(market.maintenance_margin(100).unwrap(), 5);
+        assert_eq!(market.initial_margin(101).unwrap(), 20);
+        assert_eq!(market.initial_margin(1_000).unwrap(), 200);
+        assert_eq!(market.initial_margin(1_001).unwrap(), 500);
+        assert_eq!(market.maintenance_margin(1_001).unwrap(), 250);
+    }
+
+    #[test]
+    fn margin_tiers_reject_invalid_schedules() {
+        let mut market = market();
+        assert!(market.set_margin_tiers(&[]).is_err());
+        assert!(market.set_margin_tiers(&[tier(u64::MAX, 1000, 2000)]).is_err());
+        assert!(market.set_margin_tiers(&[tier(1_000, 1000, 500), tier(100, 2000, 1000)]).is_err());
+        assert!(market.set_margin_tiers(&[tier(100, 2000, 1000), tier(1_000, 1000, 500)]).is_err());
+        assert!(market.set_margin_tiers(&[tier(u64::MAX, 1000, 500); MAX_MARGIN_TIERS + 1]).is_err());
+    }
 }
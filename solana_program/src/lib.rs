@@ -1,9 +1,16 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, MintTo, Transfer};
+use anchor_spl::token::{self, Burn, Mint, Token, TokenAccount, MintTo, Transfer};
 
 // ⚠️ REPLACE THIS WITH YOUR PROGRAM ID WHEN DEPLOYING
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
+// --- Constants ---
+
+/// Fixed-point scale for curve prices (lamports per token base unit)
+pub const PRICE_PRECISION: u128 = 1_000_000_000_000;
+/// Number of slots in the per-curve TWAP ring buffer
+pub const OBSERVATION_SLOTS: usize = 32;
+
 #[program]
 pub mod fair_launch {
     use super::*;
@@ -15,6 +22,7 @@ pub mod fair_launch {
         config.fee_bps = 100; // 1%
         config.fee_recipient = ctx.accounts.admin.key();
         config.paused = false;
+        config.set_defaults();
         msg!("Config initialized by: {}", config.admin);
         Ok(())
     }
@@ -29,6 +37,56 @@ pub mod fair_launch {
         let config = &ctx.accounts.config;
         require!(!config.paused, CustomError::ContractPaused);
 
+        // Spam protection: optional per-creator launch cooldown
+        let current_time = Clock::get()?.unix_timestamp as u64;
+        let creator_state = &mut ctx.accounts.creator_state;
+        creator_state.creator = ctx.accounts.creator.key();
+        creator_state.record_launch(config, current_time)?;
+
+        // Creation fee: SOL to the fee recipient...
+        if config.creation_fee > 0 {
+            anchor_lang::solana_program::program::invoke(
+                &anchor_lang::solana_program::system_instruction::transfer(
+                    &ctx.accounts.creator.key(),
+                    &ctx.accounts.fee_recipient.key(),
+                    config.creation_fee,
+                ),
+                &[
+                    ctx.accounts.creator.to_account_info(),
+                    ctx.accounts.fee_recipient.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+        }
+
+        // ...and/or a burn of the platform token
+        if config.creation_burn_amount > 0 {
+            let burn_mint = ctx.accounts.burn_mint
+                .as_ref()
+                .ok_or(CustomError::MissingBurnAccounts)?;
+            let creator_burn_account = ctx.accounts.creator_burn_account
+                .as_ref()
+                .ok_or(CustomError::MissingBurnAccounts)?;
+            require_keys_eq!(
+                burn_mint.key(),
+                config.creation_burn_mint,
+                CustomError::InvalidBurnMint
+            );
+
+            token::burn(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    Burn {
+                        mint: burn_mint.to_account_info(),
+                        from: creator_burn_account.to_account_info(),
+                        authority: ctx.accounts.creator.to_account_info(),
+                    },
+                ),
+                config.creation_burn_amount,
+            )?;
+        }
+
+        let curve_info = ctx.accounts.curve_state.to_account_info();
         let curve_state = &mut ctx.accounts.curve_state;
         curve_state.creator = ctx.accounts.creator.key();
         curve_state.token_mint = ctx.accounts.token_mint.key();
@@ -42,6 +100,18 @@ pub mod fair_launch {
         curve_state.market_cap_limit = 85 * 1_000_000_000; // 85 SOL target
         curve_state.graduated = false;
 
+        // Seed the TWAP oracle with the opening price
+        curve_state.created_at = current_time;
+        curve_state.bundle_slot = 0;
+        curve_state.bundle_slot_volume = 0;
+        curve_state.graduated_at = 0;
+        curve_state.price_cumulative = 0;
+        curve_state.last_price_update = current_time;
+
+        let observations = &mut ctx.accounts.observations;
+        observations.token_mint = curve_state.token_mint;
+        observations.record(current_time, 0, curve_state.spot_price()?);
+
         // Mint Supply to Vault
         let seeds = &[
             b"curve_state",
@@ -56,7 +126,7 @@ pub mod fair_launch {
                 MintTo {
                     mint: ctx.accounts.token_mint.to_account_info(),
                     to: ctx.accounts.curve_token_vault.to_account_info(),
-                    authority: ctx.accounts.curve_state.to_account_info(),
+                    authority: curve_info.clone(),
                 },
                 signer,
             ),
@@ -74,6 +144,7 @@ pub mod fair_launch {
         min_tokens_out: u64,  // Slippage protection
     ) -> Result<()> {
         let config = &ctx.accounts.config;
+        let curve_info = ctx.accounts.curve_state.to_account_info();
         let curve_state = &mut ctx.accounts.curve_state;
         
         // Pre-flight checks
@@ -88,6 +159,12 @@ pub mod fair_launch {
             current_time >= curve_state.last_trade_time + 5,
             CustomError::RateLimitExceeded
         );
+
+        // Circuit breaker: curve auto-paused after a large price move
+        require!(
+            clock.slot >= curve_state.paused_until_slot,
+            CustomError::CircuitBreakerActive
+        );
         
         // Max buy limit: 5 SOL per transaction (anti-whale)
         require!(
@@ -95,6 +172,22 @@ pub mod fair_launch {
             CustomError::ExceedsMaxBuy
         );
 
+        // Bundle guard: cap cumulative buys per slot during the launch window
+        if curve_state.record_slot_buy(config, clock.slot, current_time, amount_in_sol)? {
+            // Emitted before failing so indexers see the attempt in the tx logs
+            emit!(BundleCapTriggered {
+                token_mint: curve_state.token_mint,
+                buyer: ctx.accounts.buyer.key(),
+                slot: clock.slot,
+                slot_volume: curve_state.bundle_slot_volume,
+                attempted_amount: amount_in_sol,
+            });
+            return err!(CustomError::SlotBuyCapExceeded);
+        }
+
+        // Accrue the pre-trade price into the TWAP accumulator
+        curve_state.accrue_price(current_time)?;
+
         // 1. Calculate amounts
         let fee = amount_in_sol
             .checked_div(100)
@@ -128,18 +221,34 @@ pub mod fair_launch {
             CustomError::InsufficientLiquidity
         );
 
+        // Price impact cap (per trade)
+        let price_before = curve_state.spot_price()?;
+        let price_after = curve_price(
+            curve_state.virtual_sol_res
+                .checked_add(sol_in_after_fee)
+                .ok_or(CustomError::ArithmeticOverflow)?,
+            curve_state.virtual_token_res
+                .checked_sub(tokens_out)
+                .ok_or(CustomError::ArithmeticOverflow)?,
+        )?;
+        require!(
+            config.max_price_impact_bps == 0
+                || price_move_bps(price_before, price_after)? <= config.max_price_impact_bps as u64,
+            CustomError::PriceImpactTooHigh
+        );
+
         // 3. EFFECTS: Execute transfers FIRST (CEI Pattern)
         
         // Transfer SOL: User -> Curve PDA
         anchor_lang::solana_program::program::invoke(
             &anchor_lang::solana_program::system_instruction::transfer(
                 &ctx.accounts.buyer.key(),
-                &ctx.accounts.curve_state.to_account_info().key(),
+                &curve_info.key(),
                 sol_in_after_fee,
             ),
             &[
                 ctx.accounts.buyer.to_account_info(),
-                ctx.accounts.curve_state.to_account_info(),
+                curve_info.clone(),
                 ctx.accounts.system_program.to_account_info(),
             ],
         )?;
@@ -172,7 +281,7 @@ pub mod fair_launch {
                 Transfer {
                     from: ctx.accounts.curve_token_vault.to_account_info(),
                     to: ctx.accounts.buyer_token_account.to_account_info(),
-                    authority: ctx.accounts.curve_state.to_account_info(),
+                    authority: curve_info.clone(),
                 },
                 signer,
             ),
@@ -193,8 +302,26 @@ pub mod fair_launch {
             .checked_sub(tokens_out)
             .ok_or(CustomError::ArithmeticOverflow)?;
 
-        // Update last trade time
+        // Update last trade time and record a TWAP observation
         curve_state.last_trade_time = current_time;
+        ctx.accounts.observations.record(
+            current_time,
+            curve_state.price_cumulative,
+            price_after,
+        );
+
+        // Trip the circuit breaker if the rolling-window move is too large
+        curve_state.update_circuit_breaker(
+            config,
+            &ctx.accounts.observations,
+            price_after,
+            current_time,
+            clock.slot,
+        )?;
+
+        // Solvency invariant: vaults must cover tracked reserves
+        ctx.accounts.curve_token_vault.reload()?;
+        assert_solvent(curve_state, &ctx.accounts.curve_token_vault)?;
         
         // Emit event
         emit!(TokenPurchased {
@@ -208,8 +335,12 @@ pub mod fair_launch {
 
         msg!("Bought {} tokens for {} SOL", tokens_out, amount_in_sol);
 
-        // Check graduation
-        if curve_state.virtual_sol_res >= curve_state.market_cap_limit {
+        // Graduation is a milestone: the curve keeps trading, so holders can still
+        // exit and its TWAP stays live for perp listings
+        if curve_state.graduated_at == 0
+            && curve_state.virtual_sol_res >= curve_state.market_cap_limit
+        {
+            curve_state.graduated_at = current_time;
             emit!(GraduationReady {
                 token_mint: curve_state.token_mint,
                 final_market_cap: curve_state.virtual_sol_res,
@@ -227,6 +358,7 @@ pub mod fair_launch {
         min_sol_out: u64,  // Slippage protection
     ) -> Result<()> {
         let config = &ctx.accounts.config;
+        let curve_info = ctx.accounts.curve_state.to_account_info();
         let curve_state = &mut ctx.accounts.curve_state;
 
         // Pre-flight checks
@@ -242,6 +374,15 @@ pub mod fair_launch {
             CustomError::RateLimitExceeded
         );
 
+        // Circuit breaker: curve auto-paused after a large price move
+        require!(
+            clock.slot >= curve_state.paused_until_slot,
+            CustomError::CircuitBreakerActive
+        );
+
+        // Accrue the pre-trade price into the TWAP accumulator
+        curve_state.accrue_price(current_time)?;
+
         // 1. Calculate SOL output
         let numerator = (curve_state.virtual_sol_res as u128)
             .checked_mul(amount_tokens_in as u128)
@@ -268,6 +409,22 @@ pub mod fair_launch {
             CustomError::SlippageExceeded
         );
 
+        // Price impact cap (per trade)
+        let price_before = curve_state.spot_price()?;
+        let price_after = curve_price(
+            curve_state.virtual_sol_res
+                .checked_sub(sol_out_gross)
+                .ok_or(CustomError::ArithmeticOverflow)?,
+            curve_state.virtual_token_res
+                .checked_add(amount_tokens_in)
+                .ok_or(CustomError::ArithmeticOverflow)?,
+        )?;
+        require!(
+            config.max_price_impact_bps == 0
+                || price_move_bps(price_before, price_after)? <= config.max_price_impact_bps as u64,
+            CustomError::PriceImpactTooHigh
+        );
+
         // 2. EFFECTS: Execute transfers FIRST
         
         // Transfer Tokens: User -> Vault
@@ -294,12 +451,12 @@ pub mod fair_launch {
 
         anchor_lang::solana_program::program::invoke_signed(
             &anchor_lang::solana_program::system_instruction::transfer(
-                &ctx.accounts.curve_state.to_account_info().key(),
+                &curve_info.key(),
                 &ctx.accounts.seller.key(),
                 sol_out_net,
             ),
             &[
-                ctx.accounts.curve_state.to_account_info(),
+                curve_info.clone(),
                 ctx.accounts.seller.to_account_info(),
                 ctx.accounts.system_program.to_account_info(),
             ],
@@ -309,12 +466,12 @@ pub mod fair_launch {
         // Transfer Fee: Curve PDA -> Fee Recipient
         anchor_lang::solana_program::program::invoke_signed(
             &anchor_lang::solana_program::system_instruction::transfer(
-                &ctx.accounts.curve_state.to_account_info().key(),
+                &curve_info.key(),
                 &ctx.accounts.fee_recipient.key(),
                 fee,
             ),
             &[
-                ctx.accounts.curve_state.to_account_info(),
+                curve_info.clone(),
                 ctx.accounts.fee_recipient.to_account_info(),
                 ctx.accounts.system_program.to_account_info(),
             ],
@@ -335,8 +492,26 @@ pub mod fair_launch {
             .checked_add(amount_tokens_in)
             .ok_or(CustomError::ArithmeticOverflow)?;
 
-        // Update last trade time
+        // Update last trade time and record a TWAP observation
         curve_state.last_trade_time = current_time;
+        ctx.accounts.observations.record(
+            current_time,
+            curve_state.price_cumulative,
+            price_after,
+        );
+
+        // Trip the circuit breaker if the rolling-window move is too large
+        curve_state.update_circuit_breaker(
+            config,
+            &ctx.accounts.observations,
+            price_after,
+            current_time,
+            clock.slot,
+        )?;
+
+        // Solvency invariant: vaults must cover tracked reserves
+        ctx.accounts.curve_token_vault.reload()?;
+        assert_solvent(curve_state, &ctx.accounts.curve_token_vault)?;
         
         // Emit event
         emit!(TokenSold {
@@ -378,6 +553,200 @@ pub mod fair_launch {
         msg!("Fee recipient updated to: {}", new_recipient);
         Ok(())
     }
+
+    // 8. Update Price Risk Parameters (Admin Only)
+    pub fn set_risk_params(
+        ctx: Context<AdminAction>,
+        max_price_impact_bps: u16,
+        breaker_move_bps: u16,
+        breaker_window_secs: u64,
+        breaker_pause_slots: u64,
+    ) -> Result<()> {
+        require!(
+            max_price_impact_bps <= 10_000 && breaker_move_bps <= 10_000,
+            CustomError::InvalidParameter
+        );
+
+        let config = &mut ctx.accounts.config;
+        config.max_price_impact_bps = max_price_impact_bps;
+        config.breaker_move_bps = breaker_move_bps;
+        config.breaker_window_secs = breaker_window_secs;
+        config.breaker_pause_slots = breaker_pause_slots;
+        msg!(
+            "Risk params updated: impact {} bps, breaker {} bps / {}s, pause {} slots",
+            max_price_impact_bps,
+            breaker_move_bps,
+            breaker_window_secs,
+            breaker_pause_slots
+        );
+        Ok(())
+    }
+
+    // 9. Update Launch Bundle Guard (Admin Only)
+    pub fn set_launch_guard(
+        ctx: Context<AdminAction>,
+        launch_guard_secs: u64,
+        max_buy_per_slot: u64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.launch_guard_secs = launch_guard_secs;
+        config.max_buy_per_slot = max_buy_per_slot;
+        msg!(
+            "Launch guard updated: {} lamports per slot for {}s",
+            max_buy_per_slot,
+            launch_guard_secs
+        );
+        Ok(())
+    }
+
+    // 10. Reconcile Tracked Reserves (Admin Only)
+    pub fn reconcile(ctx: Context<Reconcile>, apply: bool) -> Result<()> {
+        let curve_state = &mut ctx.accounts.curve_state;
+        let actual_sol_res = curve_sol_balance(&curve_state.to_account_info())?;
+        let actual_token_res = ctx.accounts.curve_token_vault.amount;
+
+        emit!(ReservesReconciled {
+            token_mint: curve_state.token_mint,
+            tracked_sol_res: curve_state.real_sol_res,
+            actual_sol_res,
+            tracked_token_res: curve_state.real_token_res,
+            actual_token_res,
+            corrected: apply,
+        });
+
+        if apply {
+            // Shift the virtual reserves by the same drift so the curve never quotes
+            // against reserves it does not hold
+            let current_time = Clock::get()?.unix_timestamp as u64;
+            curve_state.accrue_price(current_time)?;
+            curve_state.virtual_sol_res =
+                shift_by_drift(curve_state.virtual_sol_res, curve_state.real_sol_res, actual_sol_res)?;
+            curve_state.virtual_token_res =
+                shift_by_drift(curve_state.virtual_token_res, curve_state.real_token_res, actual_token_res)?;
+            require!(curve_state.virtual_token_res > 0, CustomError::InsufficientLiquidity);
+            curve_state.real_sol_res = actual_sol_res;
+            curve_state.real_token_res = actual_token_res;
+
+            ctx.accounts.observations.record(
+                current_time,
+                curve_state.price_cumulative,
+                curve_state.spot_price()?,
+            );
+            msg!(
+                "Reserves corrected to {} lamports / {} tokens (virtual {} / {})",
+                actual_sol_res,
+                actual_token_res,
+                curve_state.virtual_sol_res,
+                curve_state.virtual_token_res
+            );
+        } else {
+            msg!(
+                "Reserve drift: {} -> {} lamports, {} -> {} tokens",
+                curve_state.real_sol_res,
+                actual_sol_res,
+                curve_state.real_token_res,
+                actual_token_res
+            );
+        }
+        Ok(())
+    }
+
+    // 11. Update Launch Fees & Rate Limit (Admin Only)
+    pub fn set_creation_fees(
+        ctx: Context<AdminAction>,
+        creation_fee: u64,
+        creation_burn_mint: Pubkey,
+        creation_burn_amount: u64,
+        launch_cooldown_secs: u64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.creation_fee = creation_fee;
+        config.creation_burn_mint = creation_burn_mint;
+        config.creation_burn_amount = creation_burn_amount;
+        config.launch_cooldown_secs = launch_cooldown_secs;
+        msg!(
+            "Launch fees updated: {} lamports, burn {} of {}, cooldown {}s",
+            creation_fee,
+            creation_burn_amount,
+            creation_burn_mint,
+            launch_cooldown_secs
+        );
+        Ok(())
+    }
+
+    // 12. Read TWAP (View, usable via CPI return data)
+    pub fn get_twap(ctx: Context<GetTwap>, window_secs: u64) -> Result<u128> {
+        let current_time = Clock::get()?.unix_timestamp as u64;
+        let twap = ctx.accounts.observations.twap(current_time, window_secs);
+        msg!("TWAP over {}s: {}", window_secs, twap);
+        Ok(twap)
+    }
+
+    // 13. Migrate Legacy Config (Admin Only)
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        let config_info = ctx.accounts.config.to_account_info();
+        let old_len = config_info.data_len();
+        require!(old_len < Config::LEN, CustomError::AlreadyMigrated);
+
+        grow_account(
+            &config_info,
+            &ctx.accounts.admin.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            Config::LEN,
+        )?;
+
+        // Legacy layout is a prefix of the current one, so the zeroed tail deserializes
+        let mut config = Config::try_deserialize(&mut &config_info.try_borrow_data()?[..])?;
+        require_keys_eq!(config.admin, ctx.accounts.admin.key(), CustomError::Unauthorized);
+        config.set_defaults();
+        config.try_serialize(&mut &mut config_info.try_borrow_mut_data()?[..])?;
+
+        msg!("Config migrated: {} -> {} bytes", old_len, Config::LEN);
+        Ok(())
+    }
+
+    // 14. Migrate Legacy Curve (Permissionless, payer funds the extra rent)
+    pub fn migrate_curve(ctx: Context<MigrateCurve>) -> Result<()> {
+        let curve_info = ctx.accounts.curve_state.to_account_info();
+        let old_len = curve_info.data_len();
+        require!(old_len < CurveState::LEN, CustomError::AlreadyMigrated);
+
+        grow_account(
+            &curve_info,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            CurveState::LEN,
+        )?;
+
+        let mut curve_state = CurveState::try_deserialize(&mut &curve_info.try_borrow_data()?[..])?;
+        require_keys_eq!(
+            curve_state.token_mint,
+            ctx.accounts.token_mint.key(),
+            CustomError::InvalidParameter
+        );
+
+        // Start the TWAP oracle from the current spot; launch guard is long over
+        let current_time = Clock::get()?.unix_timestamp as u64;
+        curve_state.price_cumulative = 0;
+        curve_state.last_price_update = current_time;
+        curve_state.paused_until_slot = 0;
+        curve_state.created_at = 0;
+        curve_state.bundle_slot = 0;
+        curve_state.bundle_slot_volume = 0;
+        curve_state.graduated_at = if curve_state.virtual_sol_res >= curve_state.market_cap_limit {
+            current_time
+        } else {
+            0
+        };
+        curve_state.try_serialize(&mut &mut curve_info.try_borrow_mut_data()?[..])?;
+
+        let observations = &mut ctx.accounts.observations;
+        observations.token_mint = curve_state.token_mint;
+        observations.record(current_time, 0, curve_state.spot_price()?);
+
+        msg!("Curve {} migrated: {} -> {} bytes", curve_state.token_mint, old_len, CurveState::LEN);
+        Ok(())
+    }
 }
 
 // --- Data Structures ---
@@ -388,6 +757,58 @@ pub struct Config {
     pub fee_recipient: Pubkey, // 32
     pub fee_bps: u16,         // 2
     pub paused: bool,         // 1
+    pub max_price_impact_bps: u16, // 2 (0 = no cap)
+    pub breaker_move_bps: u16,     // 2 (0 = breaker disabled)
+    pub breaker_window_secs: u64,  // 8 (Rolling, read from the observation ring)
+    pub breaker_pause_slots: u64,  // 8
+    pub launch_guard_secs: u64,    // 8 (Bundle guard window after launch)
+    pub max_buy_per_slot: u64,     // 8 (0 = no cap)
+    pub creation_fee: u64,         // 8 (Lamports per launch)
+    pub creation_burn_mint: Pubkey, // 32 (Platform token burned per launch)
+    pub creation_burn_amount: u64, // 8 (0 = no burn)
+    pub launch_cooldown_secs: u64, // 8 (Per-creator, 0 = no limit)
+}
+
+impl Config {
+    pub const LEN: usize = 8 + 32 + 32 + 2 + 1 + 2 + 2 + 8 + 8 + 8 + 8 + 8 + 32 + 8 + 8;
+
+    /// Risk and launch parameters shared by `initialize` and `migrate_config`
+    pub fn set_defaults(&mut self) {
+        self.max_price_impact_bps = 5000; // 50% per trade
+        self.breaker_move_bps = 7500;     // 75% move...
+        self.breaker_window_secs = 60;    // ...within 1 minute
+        self.breaker_pause_slots = 750;   // pauses the curve for ~5 minutes
+        self.launch_guard_secs = 180;     // Bundle guard for the first 3 minutes
+        self.max_buy_per_slot = 2 * 1_000_000_000; // 2 SOL per slot
+        self.creation_fee = 20_000_000;   // 0.02 SOL per launch
+        self.creation_burn_mint = Pubkey::default();
+        self.creation_burn_amount = 0;    // Platform token burn disabled
+        self.launch_cooldown_secs = 0;    // Per-creator rate limit disabled
+    }
+}
+
+#[account]
+pub struct CreatorState {
+    pub creator: Pubkey,       // 32
+    pub launch_count: u64,     // 8
+    pub last_launch_time: u64, // 8
+}
+
+impl CreatorState {
+    /// Counts a new launch, failing if the creator's cooldown has not elapsed
+    pub fn record_launch(&mut self, config: &Config, now: u64) -> Result<()> {
+        if self.launch_count > 0 {
+            require!(
+                now >= self.last_launch_time.saturating_add(config.launch_cooldown_secs),
+                CustomError::LaunchRateLimited
+            );
+        }
+        self.launch_count = self.launch_count
+            .checked_add(1)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        self.last_launch_time = now;
+        Ok(())
+    }
 }
 
 #[account]
@@ -402,6 +823,251 @@ pub struct CurveState {
     pub graduated: bool,            // 1
     pub bump: u8,                   // 1
     pub last_trade_time: u64,       // 8 (Rate limiting)
+    pub price_cumulative: u128,     // 16 (TWAP accumulator)
+    pub last_price_update: u64,     // 8
+    pub paused_until_slot: u64,           // 8 (Circuit breaker)
+    pub created_at: u64,                  // 8 (Bundle guard)
+    pub bundle_slot: u64,                 // 8
+    pub bundle_slot_volume: u64,          // 8
+    pub graduated_at: u64,                // 8 (When the target was first met, 0 = not yet)
+}
+
+impl CurveState {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 8 + 16 + 8 + 8 + 8 + 8 + 8 + 8;
+
+    /// Spot price implied by the virtual reserves, scaled by `PRICE_PRECISION`
+    pub fn spot_price(&self) -> Result<u128> {
+        curve_price(self.virtual_sol_res, self.virtual_token_res)
+    }
+
+    /// Adds `spot_price * elapsed` to the accumulator. Must run before reserves change.
+    pub fn accrue_price(&mut self, now: u64) -> Result<()> {
+        let elapsed = now.saturating_sub(self.last_price_update);
+        if elapsed > 0 {
+            // Wrapping on purpose: readers only ever use differences (Uniswap V2 style)
+            self.price_cumulative = self
+                .price_cumulative
+                .wrapping_add(self.spot_price()?.wrapping_mul(elapsed as u128));
+            self.last_price_update = now;
+        }
+        Ok(())
+    }
+
+    /// Adds a buy to the current slot's volume. Returns true (leaving the volume
+    /// untouched) if it would push the slot past `max_buy_per_slot` inside the
+    /// launch window.
+    pub fn record_slot_buy(&mut self, config: &Config, slot: u64, now: u64, amount: u64) -> Result<bool> {
+        if self.bundle_slot != slot {
+            self.bundle_slot = slot;
+            self.bundle_slot_volume = 0;
+        }
+        let slot_volume = self
+            .bundle_slot_volume
+            .checked_add(amount)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        let in_launch_window = now < self.created_at.saturating_add(config.launch_guard_secs);
+        if in_launch_window && config.max_buy_per_slot > 0 && slot_volume > config.max_buy_per_slot {
+            return Ok(true);
+        }
+        self.bundle_slot_volume = slot_volume;
+        Ok(false)
+    }
+
+    /// Pauses the curve if the price moved more than `breaker_move_bps` against the
+    /// price `breaker_window_secs` ago. Must run after the trade's observation is recorded.
+    pub fn update_circuit_breaker(
+        &mut self,
+        config: &Config,
+        observations: &Observations,
+        price_after: u128,
+        now: u64,
+        slot: u64,
+    ) -> Result<()> {
+        if config.breaker_move_bps == 0 {
+            return Ok(());
+        }
+
+        let window_start_price =
+            observations.price_at(now.saturating_sub(config.breaker_window_secs));
+        let move_bps = price_move_bps(window_start_price, price_after)?;
+        if move_bps > config.breaker_move_bps as u64 {
+            self.paused_until_slot = slot.saturating_add(config.breaker_pause_slots);
+            emit!(CircuitBreakerTripped {
+                token_mint: self.token_mint,
+                window_start_price,
+                price: price_after,
+                move_bps,
+                paused_until_slot: self.paused_until_slot,
+            });
+            msg!("⛔ Circuit breaker tripped: {} bps move", move_bps);
+        }
+        Ok(())
+    }
+}
+
+/// Price for a pair of virtual reserves, scaled by `PRICE_PRECISION`
+pub fn curve_price(virtual_sol_res: u64, virtual_token_res: u64) -> Result<u128> {
+    let price = (virtual_sol_res as u128)
+        .checked_mul(PRICE_PRECISION)
+        .ok_or(CustomError::ArithmeticOverflow)?
+        .checked_div(virtual_token_res as u128)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    Ok(price)
+}
+
+/// Lamports held by the curve PDA above its rent-exempt minimum
+pub fn curve_sol_balance(curve_info: &AccountInfo) -> Result<u64> {
+    let rent_exempt = Rent::get()?.minimum_balance(curve_info.data_len());
+    Ok(curve_info.lamports().saturating_sub(rent_exempt))
+}
+
+/// `virtual_res` moved by the difference between the actual and tracked real reserve
+pub fn shift_by_drift(virtual_res: u64, tracked: u64, actual: u64) -> Result<u64> {
+    let shifted = if actual >= tracked {
+        virtual_res.checked_add(actual - tracked)
+    } else {
+        virtual_res.checked_sub(tracked - actual)
+    };
+    Ok(shifted.ok_or(CustomError::ArithmeticOverflow)?)
+}
+
+/// Fails unless the curve PDA and token vault hold at least the tracked real reserves
+pub fn assert_solvent(curve_state: &Account<CurveState>, vault: &TokenAccount) -> Result<()> {
+    require!(
+        curve_sol_balance(&curve_state.to_account_info())? >= curve_state.real_sol_res,
+        CustomError::SolvencyViolation
+    );
+    require!(
+        vault.amount >= curve_state.real_token_res,
+        CustomError::SolvencyViolation
+    );
+    Ok(())
+}
+
+/// Reallocs a program-owned PDA to `new_len`, with `payer` covering the extra rent.
+/// Only the rent delta is added so SOL held above rent (curve reserves) is unchanged.
+pub fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    new_len: usize,
+) -> Result<()> {
+    let rent = Rent::get()?;
+    let extra_rent = rent
+        .minimum_balance(new_len)
+        .saturating_sub(rent.minimum_balance(account.data_len()));
+    if extra_rent > 0 {
+        anchor_lang::solana_program::program::invoke(
+            &anchor_lang::solana_program::system_instruction::transfer(
+                payer.key,
+                account.key,
+                extra_rent,
+            ),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    account.realloc(new_len, true)?;
+    Ok(())
+}
+
+/// Absolute price change between two prices, in basis points of `from`
+pub fn price_move_bps(from: u128, to: u128) -> Result<u64> {
+    let bps = to
+        .abs_diff(from)
+        .checked_mul(10_000)
+        .ok_or(CustomError::ArithmeticOverflow)?
+        .checked_div(from)
+        .ok_or(CustomError::ArithmeticOverflow)?;
+    Ok(bps.min(u64::MAX as u128) as u64)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct Observation {
+    pub timestamp: u64,         // 8
+    pub price_cumulative: u128, // 16
+}
+
+#[account]
+pub struct Observations {
+    pub token_mint: Pubkey,                             // 32
+    pub index: u16,                                     // 2 (Most recent slot)
+    pub last_price: u128,                               // 16 (Spot after latest trade)
+    pub observations: [Observation; OBSERVATION_SLOTS], // 24 * 32
+}
+
+impl Observations {
+    /// Writes a new observation, overwriting the latest one if it shares a timestamp
+    pub fn record(&mut self, timestamp: u64, price_cumulative: u128, last_price: u128) {
+        let latest = self.observations[self.index as usize];
+        if latest.timestamp != 0 && latest.timestamp != timestamp {
+            self.index = ((self.index as usize + 1) % OBSERVATION_SLOTS) as u16;
+        }
+        self.observations[self.index as usize] = Observation {
+            timestamp,
+            price_cumulative,
+        };
+        self.last_price = last_price;
+    }
+
+    /// Timestamp of the most recent observation (the curve's last trade)
+    pub fn last_update(&self) -> u64 {
+        self.observations[self.index as usize].timestamp
+    }
+
+    /// TWAP over the last `window` seconds, or since the oldest observation if the
+    /// buffer does not reach back that far
+    pub fn twap(&self, now: u64, window: u64) -> u128 {
+        let latest = self.observations[self.index as usize];
+        let cumulative_now = latest.price_cumulative.wrapping_add(
+            self.last_price
+                .wrapping_mul(now.saturating_sub(latest.timestamp) as u128),
+        );
+
+        // Walk backwards to the newest observation at or before the window start
+        let target = now.saturating_sub(window);
+        let mut start = latest;
+        for i in 1..OBSERVATION_SLOTS {
+            let idx = (self.index as usize + OBSERVATION_SLOTS - i) % OBSERVATION_SLOTS;
+            let obs = self.observations[idx];
+            if start.timestamp <= target || obs.timestamp == 0 {
+                break;
+            }
+            start = obs;
+        }
+
+        let elapsed = now.saturating_sub(start.timestamp);
+        if elapsed == 0 {
+            return self.last_price;
+        }
+        cumulative_now.wrapping_sub(start.price_cumulative) / elapsed as u128
+    }
+
+    /// Spot price in effect at `timestamp`, recovered from the accumulator delta of the
+    /// surrounding observations. Falls back to the oldest known price if the buffer
+    /// does not reach back that far.
+    pub fn price_at(&self, timestamp: u64) -> u128 {
+        let mut newer = self.observations[self.index as usize];
+        let mut price = self.last_price;
+        if newer.timestamp <= timestamp {
+            return price;
+        }
+
+        for i in 1..OBSERVATION_SLOTS {
+            let idx = (self.index as usize + OBSERVATION_SLOTS - i) % OBSERVATION_SLOTS;
+            let obs = self.observations[idx];
+            if obs.timestamp == 0 || obs.timestamp >= newer.timestamp {
+                break;
+            }
+            // Price held constant from `obs` until the next trade at `newer`
+            price = newer.price_cumulative.wrapping_sub(obs.price_cumulative)
+                / (newer.timestamp - obs.timestamp) as u128;
+            if obs.timestamp <= timestamp {
+                break;
+            }
+            newer = obs;
+        }
+        price
+    }
 }
 
 // --- Contexts ---
@@ -411,7 +1077,7 @@ pub struct Initialize<'info> {
     #[account(
         init,
         payer = admin,
-        space = 8 + 32 + 32 + 2 + 1,
+        space = Config::LEN,
         seeds = [b"config"],
         bump
     )]
@@ -428,6 +1094,28 @@ pub struct CreateToken<'info> {
     pub creator: Signer<'info>,
     
     pub config: Account<'info, Config>,
+
+    #[account(
+        init_if_needed,
+        payer = creator,
+        space = 8 + 32 + 8 + 8,
+        seeds = [b"creator", creator.key().as_ref()],
+        bump
+    )]
+    pub creator_state: Account<'info, CreatorState>,
+
+    #[account(
+        mut,
+        constraint = fee_recipient.key() == config.fee_recipient @ CustomError::InvalidFeeRecipient
+    )]
+    /// CHECK: Validated via constraint
+    pub fee_recipient: AccountInfo<'info>,
+
+    // Only required when config.creation_burn_amount > 0
+    #[account(mut)]
+    pub burn_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub creator_burn_account: Option<Account<'info, TokenAccount>>,
     
     #[account(
         init,
@@ -441,12 +1129,21 @@ pub struct CreateToken<'info> {
     #[account(
         init,
         payer = creator,
-        space = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 8,
+        space = CurveState::LEN,
         seeds = [b"curve_state", token_mint.key().as_ref()],
         bump
     )]
     pub curve_state: Account<'info, CurveState>,
 
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + 32 + 2 + 16 + 24 * OBSERVATION_SLOTS,
+        seeds = [b"observations", token_mint.key().as_ref()],
+        bump
+    )]
+    pub observations: Box<Account<'info, Observations>>,
+
     #[account(
         init,
         payer = creator,
@@ -472,11 +1169,22 @@ pub struct BuyTokens<'info> {
     #[account(mut)]
     pub curve_state: Account<'info, CurveState>,
     
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [b"token_vault", curve_state.token_mint.as_ref()],
+        bump
+    )]
     pub curve_token_vault: Account<'info, TokenAccount>,
     
     #[account(mut)]
     pub buyer_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"observations", curve_state.token_mint.as_ref()],
+        bump
+    )]
+    pub observations: Box<Account<'info, Observations>>,
     
     // FIX #1: Validate fee recipient matches config
     #[account(
@@ -500,12 +1208,23 @@ pub struct SellTokens<'info> {
     #[account(mut)]
     pub curve_state: Account<'info, CurveState>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [b"token_vault", curve_state.token_mint.as_ref()],
+        bump
+    )]
     pub curve_token_vault: Account<'info, TokenAccount>,
 
     #[account(mut)]
     pub seller_token_account: Account<'info, TokenAccount>,
 
+    #[account(
+        mut,
+        seeds = [b"observations", curve_state.token_mint.as_ref()],
+        bump
+    )]
+    pub observations: Box<Account<'info, Observations>>,
+
     // FIX #1: Validate fee recipient
     #[account(
         mut,
@@ -528,6 +1247,72 @@ pub struct AdminAction<'info> {
     pub admin: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct GetTwap<'info> {
+    pub observations: Box<Account<'info, Observations>>,
+}
+
+#[derive(Accounts)]
+pub struct Reconcile<'info> {
+    #[account(has_one = admin @ CustomError::Unauthorized)]
+    pub config: Account<'info, Config>,
+    pub admin: Signer<'info>,
+
+    #[account(mut)]
+    pub curve_state: Account<'info, CurveState>,
+
+    #[account(
+        seeds = [b"token_vault", curve_state.token_mint.as_ref()],
+        bump
+    )]
+    pub curve_token_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"observations", curve_state.token_mint.as_ref()],
+        bump
+    )]
+    pub observations: Box<Account<'info, Observations>>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    #[account(mut, owner = crate::ID, seeds = [b"config"], bump)]
+    /// CHECK: Legacy layout, owner and discriminator checked on deserialize
+    pub config: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateCurve<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"curve_state", token_mint.key().as_ref()],
+        bump
+    )]
+    /// CHECK: Legacy layout, owner and discriminator checked on deserialize
+    pub curve_state: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 32 + 2 + 16 + 24 * OBSERVATION_SLOTS,
+        seeds = [b"observations", token_mint.key().as_ref()],
+        bump
+    )]
+    pub observations: Box<Account<'info, Observations>>,
+
+    pub system_program: Program<'info, System>,
+}
+
 // --- Events ---
 
 #[event]
@@ -556,6 +1341,34 @@ pub struct GraduationReady {
     pub final_market_cap: u64,
 }
 
+#[event]
+pub struct CircuitBreakerTripped {
+    pub token_mint: Pubkey,
+    pub window_start_price: u128,
+    pub price: u128,
+    pub move_bps: u64,
+    pub paused_until_slot: u64,
+}
+
+#[event]
+pub struct ReservesReconciled {
+    pub token_mint: Pubkey,
+    pub tracked_sol_res: u64,
+    pub actual_sol_res: u64,
+    pub tracked_token_res: u64,
+    pub actual_token_res: u64,
+    pub corrected: bool,
+}
+
+#[event]
+pub struct BundleCapTriggered {
+    pub token_mint: Pubkey,
+    pub buyer: Pubkey,
+    pub slot: u64,
+    pub slot_volume: u64,
+    pub attempted_amount: u64,
+}
+
 // --- Errors ---
 
 #[error_code]
@@ -582,4 +1395,233 @@ pub enum CustomError {
     RateLimitExceeded,
     #[msg("Exceeds maximum buy limit (5 SOL)")]
     ExceedsMaxBuy,
+    #[msg("Trade exceeds the maximum price impact")]
+    PriceImpactTooHigh,
+    #[msg("Circuit breaker active: curve is temporarily paused")]
+    CircuitBreakerActive,
+    #[msg("Invalid parameter")]
+    InvalidParameter,
+    #[msg("Per-slot buy cap reached during launch window")]
+    SlotBuyCapExceeded,
+    #[msg("Vault balances do not cover tracked reserves")]
+    SolvencyViolation,
+    #[msg("Launch rate limit: creator cooldown not elapsed")]
+    LaunchRateLimited,
+    #[msg("Platform token burn accounts missing")]
+    MissingBurnAccounts,
+    #[msg("Burn mint does not match config")]
+    InvalidBurnMint,
+    #[msg("Account already uses the current layout")]
+    AlreadyMigrated,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observations() -> Observations {
+        Observations {
+            token_mint: Pubkey::default(),
+            index: 0,
+            last_price: 0,
+            observations: [Observation::default(); OBSERVATION_SLOTS],
+        }
+    }
+
+    fn config() -> Config {
+        let mut config = Config {
+            admin: Pubkey::default(),
+            fee_recipient: Pubkey::default(),
+            fee_bps: 100,
+            paused: false,
+            max_price_impact_bps: 0,
+            breaker_move_bps: 0,
+            breaker_window_secs: 0,
+            breaker_pause_slots: 0,
+            launch_guard_secs: 0,
+            max_buy_per_slot: 0,
+            creation_fee: 0,
+            creation_burn_mint: Pubkey::default(),
+            creation_burn_amount: 0,
+            launch_cooldown_secs: 0,
+        };
+        config.set_defaults();
+        config
+    }
+
+    /// Fresh curve as set up by `create_token` at t=100
+    fn curve() -> CurveState {
+        CurveState {
+            creator: Pubkey::default(),
+            token_mint: Pubkey::default(),
+            virtual_sol_res: 30 * 1_000_000_000,
+            virtual_token_res: 1_073_000_000 * 1_000_000,
+            real_sol_res: 0,
+            real_token_res: 1_000_000_000 * 1_000_000,
+            market_cap_limit: 85 * 1_000_000_000,
+            graduated: false,
+            bump: 0,
+            last_trade_time: 0,
+            price_cumulative: 0,
+            last_price_update: 100,
+            paused_until_slot: 0,
+            created_at: 100,
+            bundle_slot: 0,
+            bundle_slot_volume: 0,
+            graduated_at: 0,
+        }
+    }
+
+    /// Ring with price 10 from t=100, 20 from t=200 and 40 from t=300
+    fn traded() -> Observations {
+        let mut obs = observations();
+        obs.record(100, 0, 10);
+        obs.record(200, 1_000, 20);
+        obs.record(300, 3_000, 40);
+        obs
+    }
+
+    #[test]
+    fn twap_averages_over_the_window() {
+        let obs = traded();
+        // Extrapolates the latest price to `now`
+        assert_eq!(obs.twap(400, 100), 40);
+        // [200, 400): 100s at 20, 100s at 40
+        assert_eq!(obs.twap(400, 200), 30);
+        // Window older than the ring falls back to the oldest observation
+        assert_eq!(obs.twap(400, 1_000), (1_000 + 2_000 + 4_000) / 300);
+    }
+
+    #[test]
+    fn twap_of_a_fresh_curve_is_its_spot() {
+        let mut obs = observations();
+        obs.record(100, 0, 10);
+        assert_eq!(obs.twap(100, 60), 10);
+        assert_eq!(obs.twap(160, 60), 10);
+    }
+
+    #[test]
+    fn record_overwrites_same_timestamp_and_wraps() {
+        let mut obs = observations();
+        obs.record(100, 0, 10);
+        obs.record(100, 0, 12);
+        assert_eq!(obs.index, 0);
+        assert_eq!(obs.last_price, 12);
+
+        for i in 1..=OBSERVATION_SLOTS as u64 {
+            obs.record(100 + i, 0, 12);
+        }
+        assert_eq!(obs.index, 0);
+        assert_eq!(obs.last_update(), 100 + OBSERVATION_SLOTS as u64);
+    }
+
+    #[test]
+    fn accrue_price_integrates_spot_over_time() {
+        let mut curve = curve();
+        let spot = curve.spot_price().unwrap();
+        curve.accrue_price(110).unwrap();
+        assert_eq!(curve.price_cumulative, spot * 10);
+        assert_eq!(curve.last_price_update, 110);
+        // Same timestamp accrues nothing
+        curve.accrue_price(110).unwrap();
+        assert_eq!(curve.price_cumulative, spot * 10);
+    }
+
+    #[test]
+    fn price_at_reads_the_price_in_effect() {
+        let obs = traded();
+        assert_eq!(obs.price_at(350), 40);
+        assert_eq!(obs.price_at(300), 40);
+        assert_eq!(obs.price_at(250), 20);
+        assert_eq!(obs.price_at(150), 10);
+        // Older than the ring: oldest known price
+        assert_eq!(obs.price_at(50), 10);
+    }
+
+    #[test]
+    fn price_move_is_symmetric_in_bps_of_the_start() {
+        assert_eq!(price_move_bps(100, 150).unwrap(), 5_000);
+        assert_eq!(price_move_bps(100, 50).unwrap(), 5_000);
+        assert!(price_move_bps(0, 50).is_err());
+    }
+
+    #[test]
+    fn breaker_compares_against_the_rolling_window_start() {
+        let config = config(); // 75% within 60s pauses for 750 slots
+        let obs = traded();
+        let mut curve = curve();
+
+        // 40 vs 20 sixty seconds before t=260: +100%
+        curve.update_circuit_breaker(&config, &obs, 40, 260, 1_000).unwrap();
+        assert_eq!(curve.paused_until_slot, 1_750);
+
+        // 40 vs 40 sixty seconds before t=400: no move
+        let mut curve = self::curve();
+        curve.update_circuit_breaker(&config, &obs, 40, 400, 1_000).unwrap();
+        assert_eq!(curve.paused_until_slot, 0);
+    }
+
+    #[test]
+    fn slot_buy_cap_accumulates_within_a_slot() {
+        let config = config(); // 2 SOL per slot for the first 180s
+        let mut curve = curve();
+        assert!(!curve.record_slot_buy(&config, 7, 110, 1_500_000_000).unwrap());
+        assert!(curve.record_slot_buy(&config, 7, 110, 1_000_000_000).unwrap());
+        assert_eq!(curve.bundle_slot_volume, 1_500_000_000);
+        assert!(!curve.record_slot_buy(&config, 7, 110, 500_000_000).unwrap());
+
+        // A new slot starts from zero
+        assert!(!curve.record_slot_buy(&config, 8, 111, 2_000_000_000).unwrap());
+        assert_eq!(curve.bundle_slot_volume, 2_000_000_000);
+    }
+
+    #[test]
+    fn slot_buy_cap_only_applies_in_the_launch_window() {
+        let mut config = config();
+        let mut curve = curve();
+        assert!(!curve.record_slot_buy(&config, 7, 280, 5_000_000_000).unwrap());
+
+        config.max_buy_per_slot = 0;
+        assert!(!curve.record_slot_buy(&config, 9, 110, 5_000_000_000).unwrap());
+    }
+
+    #[test]
+    fn drift_shifts_virtual_reserves_both_ways() {
+        assert_eq!(shift_by_drift(1_000, 100, 150).unwrap(), 1_050);
+        assert_eq!(shift_by_drift(1_000, 100, 40).unwrap(), 940);
+        assert_eq!(shift_by_drift(1_000, 100, 100).unwrap(), 1_000);
+        assert!(shift_by_drift(10, 100, 40).is_err());
+    }
+
+    #[test]
+    fn creator_cooldown_spaces_launches() {
+        let mut config = config();
+        config.launch_cooldown_secs = 60;
+        let mut creator = CreatorState {
+            creator: Pubkey::default(),
+            launch_count: 0,
+            last_launch_time: 0,
+        };
+        // The first launch is never rate limited
+        creator.record_launch(&config, 10).unwrap();
+        assert_eq!(
+            creator.record_launch(&config, 69).unwrap_err(),
+            CustomError::LaunchRateLimited.into()
+        );
+        assert_eq!(creator.launch_count, 1);
+        creator.record_launch(&config, 70).unwrap();
+        assert_eq!((creator.launch_count, creator.last_launch_time), (2, 70));
+
+        // Disabled by default
+        config.set_defaults();
+        creator.record_launch(&config, 70).unwrap();
+        assert_eq!(creator.launch_count, 3);
+    }
+
+    #[test]
+    fn launches_charge_the_creation_fee_by_default() {
+        let config = config();
+        assert_eq!(config.creation_fee, 20_000_000);
+        assert_eq!(config.creation_burn_amount, 0);
+    }
 }